@@ -2,7 +2,7 @@ use std::fs;
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Entity(u64);
 
 impl Entity {
@@ -31,20 +31,157 @@ pub enum Shape {
         y: [f32; 3],
         h: f32,
     },
+    Sphere {
+        c: [f32; 3],
+        r: f32,
+    },
+    Capsule {
+        a: [f32; 3],
+        b: [f32; 3],
+        r: f32,
+    },
+    Compound {
+        parts: Vec<Shape>,
+    },
+    TriangleMesh {
+        vertices: Vec<[f32; 3]>,
+        indices: Vec<u32>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhysicsMaterial {
+    pub restitution: f32,
+    pub static_friction: f32,
+    pub dynamic_friction: f32,
+}
+
+impl Default for PhysicsMaterial {
+    fn default() -> Self {
+        Self {
+            restitution: 0.0,
+            static_friction: 0.6,
+            dynamic_friction: 0.4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BodyType {
+    Static,
+    Kinematic,
+    Dynamic,
+}
+
+fn default_category() -> u32 {
+    1
+}
+
+fn default_gravity_scale() -> f32 {
+    1.0
+}
+
+// Older levels only had a has_gravity flag, which maps to a scale of 1 or 0
+fn gravity_scale_or_flag<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<f32, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum GravityScale {
+        Scale(f32),
+        Flag(bool),
+    }
+    Ok(match GravityScale::deserialize(deserializer)? {
+        GravityScale::Scale(scale) => scale,
+        GravityScale::Flag(true) => 1.0,
+        GravityScale::Flag(false) => 0.0,
+    })
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PhysicsRb {
+    // Lets joints refer to this body
+    #[serde(default)]
+    pub name: Option<String>,
+    // Dynamic unless mass is inf, which still marks a static body
+    #[serde(default)]
+    pub body_type: Option<BodyType>,
+    // At most one of these, bodies without either get a density of 1. A
+    // mass of 0 counts as left out.
+    #[serde(default)]
     pub mass: f32,
+    #[serde(default)]
+    pub density: Option<f32>,
     pub shape: Shape,
-    pub has_gravity: bool,
+    #[serde(
+        default = "default_gravity_scale",
+        alias = "has_gravity",
+        deserialize_with = "gravity_scale_or_flag"
+    )]
+    pub gravity_scale: f32,
+    #[serde(default)]
+    pub can_rotate: bool,
     pub init_location: [f32; 3],
-    pub no_interact_mask: u32,
+    #[serde(default = "default_category")]
+    pub category: u32,
+    // Categories this body doesn't collide with, older levels call it
+    // no_interact_mask
+    #[serde(default, alias = "no_interact_mask")]
+    pub ignore: u32,
+    #[serde(default)]
+    pub material: PhysicsMaterial,
+    #[serde(default)]
+    pub ccd: bool,
+    #[serde(default)]
+    pub is_sensor: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JointMotor {
+    pub target_velocity: f32,
+    pub max_force: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum JointKind {
+    Fixed,
+    BallSocket,
+    Hinge {
+        axis: [f32; 3],
+        #[serde(default)]
+        limits: Option<[f32; 2]>,
+        #[serde(default)]
+        motor: Option<JointMotor>,
+    },
+    Prismatic {
+        axis: [f32; 3],
+        #[serde(default)]
+        limits: Option<[f32; 2]>,
+        #[serde(default)]
+        motor: Option<JointMotor>,
+    },
+    Distance {
+        min: f32,
+        max: f32,
+    },
+}
+
+// Connects two named bodies. Anchors and axes are in world space with the
+// bodies at their initial locations, anchor_b defaults to anchor_a.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhysicsJoint {
+    pub body_a: String,
+    pub body_b: String,
+    pub anchor_a: [f32; 3],
+    #[serde(default)]
+    pub anchor_b: Option<[f32; 3]>,
+    pub kind: JointKind,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Node {
     PhysicsRb(PhysicsRb),
+    Joint(PhysicsJoint),
 }
 
 #[derive(Serialize, Deserialize)]
@@ -3,13 +3,18 @@ use std::sync::Arc;
 // use physics::PhysicsManager;
 use crate::inputs::Inputs;
 
-use common::{Entity, Level, Node, PhysicsRb, Shape};
-use glam::{Mat4, Vec3};
+use common::{Entity, Level, Node, Shape};
+use glam::Vec3;
 use indexmap::IndexMap;
 use physics::{
-    Kinematics, Orientation, PhysicsManager, RigidBody, collision_shape::CollisionShape,
+    PhysicsManager,
+    character::CharacterController,
+    debug_draw::{DebugDrawOptions, DebugLines},
+};
+use rendering::{
+    RenderingManager,
+    tex_mesh::{Mesh, Vertex},
 };
-use rendering::{RenderingManager, tex_mesh::Mesh};
 use winit::{
     keyboard::{KeyCode, PhysicalKey},
     window::{CursorGrabMode, Window},
@@ -19,12 +24,19 @@ pub struct Game {
     pub(crate) renderer_system: RenderingManager,
     physics_system: PhysicsManager,
     entities: Vec<Entity>,
-    physics_rbs: IndexMap<Entity, RigidBody>,
     // camera: Cam3d,
     window: Arc<Window>,
     is_cursor_grabbed: bool,
+    // Walking with the character instead of flying the camera around
+    is_walking: bool,
+    character: CharacterController,
+    // Physics shapes, contacts and joints drawn over the scene
+    is_debug_drawing: bool,
+    debug_draw_options: DebugDrawOptions,
 }
 
+const WALK_SPEED: f32 = 4.0;
+
 impl Game {
     pub fn new(window: Arc<Window>) -> anyhow::Result<Self> {
         let renderer_system = RenderingManager::new(&window)?;
@@ -42,10 +54,13 @@ impl Game {
             renderer_system,
             physics_system,
             entities: Default::default(),
-            physics_rbs: Default::default(),
             // camera, d
             window,
             is_cursor_grabbed: true,
+            is_walking: false,
+            character: CharacterController::new(Vec3::ZERO, 0.3, 0.6),
+            is_debug_drawing: false,
+            debug_draw_options: DebugDrawOptions::default(),
         })
     }
 
@@ -82,68 +97,54 @@ impl Game {
                 glam::Vec3::from(*y),
                 *h,
             ),
-        }
-    }
-
-    fn import_rb(rb: &PhysicsRb) -> RigidBody {
-        let shape = match &rb.shape {
-            Shape::Rectangle { c, x, y } => CollisionShape::new_rect(
-                Vec3::from_array(*c),
-                Vec3::from_array(*x),
-                Vec3::from_array(*y),
-            ),
-            Shape::Cube { c, x, y, h } => CollisionShape::new_cube(
-                Vec3::from_array(*c),
-                Vec3::from_array(*x),
-                Vec3::from_array(*y),
-                *h,
+            Shape::Sphere { c, r } => Mesh::new_uv_sphere(glam::Vec3::from(*c), *r),
+            Shape::Capsule { a, b, r } => {
+                Mesh::new_capsule(glam::Vec3::from(*a), glam::Vec3::from(*b), *r)
+            }
+            Shape::Compound { parts } => {
+                Mesh::merge(parts.iter().map(Self::shape_to_mesh).collect())
+            }
+            // Renderer index buffers are u16, indices were checked when the
+            // collision shape was built
+            Shape::TriangleMesh { vertices, indices } => Mesh::new_triangle_mesh(
+                &vertices
+                    .iter()
+                    .map(|v| Vec3::from_array(*v))
+                    .collect::<Vec<_>>(),
+                &indices.iter().map(|i| *i as u16).collect::<Vec<_>>(),
             ),
-        };
-        let orient = Orientation {
-            translation: Vec3::from_array(rb.init_location),
-            rotation: Mat4::IDENTITY,
-        };
-        RigidBody::new(
-            rb.mass,
-            Arc::new(shape),
-            orient,
-            Kinematics::new(),
-            false,
-            rb.has_gravity,
-            rb.no_interact_mask,
-        )
+        }
     }
 
     pub fn load_level(&mut self) -> anyhow::Result<()> {
         let level = Level::from_file("data/levels/2.ron")?;
+        let physics_system =
+            PhysicsManager::from_level(&level, self.physics_system.config.clone())?;
         self.entities.clear();
+        // Same numbering the physics world gave the bodies
         let mut next_ent_id = 0;
         let mut gpu_meshes = IndexMap::new();
-        let mut physics_rbs = IndexMap::new();
         for node in &level.nodes {
-            match node {
-                Node::PhysicsRb(physics_rb) => {
-                    let mesh = Self::shape_to_mesh(&physics_rb.shape);
-                    let gpu_mesh = self.renderer_system.load_mesh(mesh)?;
-                    let rb = Self::import_rb(physics_rb);
-                    let ent = Entity::new(next_ent_id);
-                    gpu_meshes.insert(ent, gpu_mesh);
-                    physics_rbs.insert(ent, rb);
-                    self.entities.push(ent);
-                    next_ent_id += 1;
-                }
+            let Node::PhysicsRb(physics_rb) = node else {
+                continue;
+            };
+            let ent = Entity::new(next_ent_id);
+            // Sensors are invisible trigger volumes
+            if !physics_rb.is_sensor {
+                let mesh = Self::shape_to_mesh(&physics_rb.shape);
+                gpu_meshes.insert(ent, self.renderer_system.load_mesh(mesh)?);
             }
+            self.entities.push(ent);
+            next_ent_id += 1;
         }
-        self.physics_rbs = physics_rbs;
+        self.physics_system = physics_system;
         self.renderer_system.meshes = gpu_meshes;
 
         Ok(())
     }
 
-    fn camera_move(&mut self, frame_time: u128, front: i32, right: i32, up: i32) {
-        let mvmt = 0.002 * (frame_time as f32);
-        self.renderer_system.camera.eye.y += up as f32 * mvmt;
-
+    // Front and right directions of the camera flattened onto the ground
+    fn camera_ground_axes(&self) -> (Vec3, Vec3) {
         let mut dir_proj = self.renderer_system.camera.dir;
         dir_proj.y = 0.0;
         if dir_proj.x == 0.0 && dir_proj.z == 0.0 {
@@ -152,15 +153,68 @@ impl Game {
         }
         let x = dir_proj.normalize();
         let y = glam::vec3(-x.z, 0.0, x.x);
+        (x, y)
+    }
+
+    fn camera_move(&mut self, frame_time: u128, front: i32, right: i32, up: i32) {
+        let mvmt = 0.002 * (frame_time as f32);
+        self.renderer_system.camera.eye.y += up as f32 * mvmt;
+
+        let (x, y) = self.camera_ground_axes();
         self.renderer_system.camera.eye += front as f32 * x * mvmt;
         self.renderer_system.camera.eye += right as f32 * y * mvmt;
     }
 
+    fn eye_offset(&self) -> Vec3 {
+        Vec3::Y * (self.character.half_height + self.character.radius * 0.8)
+    }
+
+    fn toggle_walking(&mut self) {
+        self.is_walking = !self.is_walking;
+        if self.is_walking {
+            self.character.position = self.renderer_system.camera.eye - self.eye_offset();
+            self.character.velocity = Vec3::ZERO;
+        }
+    }
+
+    fn character_move(&mut self, frame_time: u128, front: i32, right: i32, jump: bool) {
+        let (x, y) = self.camera_ground_axes();
+        let walk = (front as f32 * x + right as f32 * y).normalize_or_zero() * WALK_SPEED;
+        if jump {
+            self.character.jump();
+        }
+        self.physics_system
+            .move_character(&mut self.character, walk, frame_time as f32 / 1000.0);
+        self.renderer_system.camera.eye = self.character.position + self.eye_offset();
+    }
+
+    fn update_debug_lines(&mut self) {
+        self.renderer_system.debug_lines.clear();
+        if !self.is_debug_drawing {
+            return;
+        }
+        let mut lines = DebugLines::default();
+        self.physics_system
+            .debug_draw(&self.debug_draw_options, &mut lines);
+        self.renderer_system.debug_lines.extend(
+            lines
+                .lines
+                .into_iter()
+                .flat_map(|[from, to, color]| [from, to].map(|pos| Vertex { pos, color })),
+        );
+    }
+
     pub fn run(&mut self, frame_time: u128, inputs: &mut Inputs) -> anyhow::Result<()> {
         let mouse_move = inputs.mouse_delta();
         if inputs.key_pressed_this_frame(PhysicalKey::Code(KeyCode::KeyG)) {
             self.toggle_mouse_grab();
         }
+        if inputs.key_pressed_this_frame(PhysicalKey::Code(KeyCode::KeyV)) {
+            self.toggle_walking();
+        }
+        if inputs.key_pressed_this_frame(PhysicalKey::Code(KeyCode::KeyB)) {
+            self.is_debug_drawing = !self.is_debug_drawing;
+        }
         if inputs.key_pressed_this_frame(PhysicalKey::Code(KeyCode::KeyR)) {
             println!("refreshing level");
             self.load_level()
@@ -188,7 +242,9 @@ impl Game {
         if inputs.key_pressed(PhysicalKey::Code(KeyCode::KeyA)) {
             right -= 1;
         }
-        self.camera_move(frame_time, front, right, up);
+        if !self.is_walking {
+            self.camera_move(frame_time, front, right, up);
+        }
         if self.is_cursor_grabbed {
             self.renderer_system
                 .camera
@@ -197,23 +253,24 @@ impl Game {
                 .camera
                 .move_up_down(glam::Vec3::Y, 0.01 * mouse_move.1 as f32);
         }
-        for _ in 0..frame_time {
-            for rb in self.physics_rbs.values_mut() {
-                if rb.has_gravity {
-                    rb.kinematics.acceleration.y = -10.0;
-                }
-            }
-            self.physics_system.run_ms(&mut self.physics_rbs);
+        self.physics_system.update(frame_time as f32 / 1000.0);
+        for event in self.physics_system.drain_events() {
+            log::debug!("physics event: {event:?}");
+        }
+        if self.is_walking {
+            self.character_move(frame_time, front, right, up > 0);
         }
+        let alpha = self.physics_system.interpolation_alpha();
         for ent in &self.entities {
-            let Some(rb) = self.physics_rbs.get(ent) else {
+            let Some(rb) = self.physics_system.body_of(*ent) else {
                 continue;
             };
             let Some(gpu_mesh) = self.renderer_system.meshes.get_mut(ent) else {
                 continue;
             };
-            gpu_mesh.tr = rb.orient.to_transform();
+            gpu_mesh.tr = rb.interpolated_transform(alpha);
         }
+        self.update_debug_lines();
         self.renderer_system.render()?;
         inputs.advance_frame();
         Ok(())
@@ -100,11 +100,8 @@ impl ApplicationHandler for App {
         _device_id: winit::event::DeviceId,
         event: winit::event::DeviceEvent,
     ) {
-        match event {
-            DeviceEvent::MouseMotion { delta } => {
-                self.inputs.add_mouse_delta(delta);
-            }
-            _ => {}
+        if let DeviceEvent::MouseMotion { delta } = event {
+            self.inputs.add_mouse_delta(delta);
         }
     }
 
@@ -47,8 +47,8 @@ pub enum Captured<'a, T> {
     Rc(&'a Rc<T>),
 }
 
-impl<'a, T> Captured<'a, T> {
-    pub fn as_ref(&self) -> &T {
+impl<T> AsRef<T> for Captured<'_, T> {
+    fn as_ref(&self) -> &T {
         match self {
             Captured::Obj(t) => t,
             Captured::Arc(t) => t.as_ref(),
@@ -77,7 +77,7 @@ pub trait GpuDevice {
     fn new_sampler(&self) -> Self::S;
     fn new_bind_group_layout(&self, bindings: Vec<BindingDesc>) -> Self::BGL;
     fn new_bind_group(&self, layout: &Self::BGL) -> Self::BG;
-    fn new_render_pipeline<'a>(
+    fn new_render_pipeline(
         &self,
         vert_info: VertexStage,
         frag_info: FragmentStage,
@@ -0,0 +1,53 @@
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    pub fn from_points(points: &[Vec3]) -> Self {
+        let mut out = Self::new(Vec3::INFINITY, Vec3::NEG_INFINITY);
+        for &p in points {
+            out.min = out.min.min(p);
+            out.max = out.max.max(p);
+        }
+        out
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self::new(self.min.min(other.min), self.max.max(other.max))
+    }
+
+    pub fn expanded(&self, margin: f32) -> Self {
+        Self::new(
+            self.min - Vec3::splat(margin),
+            self.max + Vec3::splat(margin),
+        )
+    }
+
+    // Box covering this one moved along translation
+    pub fn swept(&self, translation: Vec3) -> Self {
+        self.union(&Self::new(self.min + translation, self.max + translation))
+    }
+
+    // Gap between the boxes, 0 when they overlap
+    pub fn distance(&self, other: &Self) -> f32 {
+        let gap = (other.min - self.max).max(self.min - other.max);
+        gap.max(Vec3::ZERO).length()
+    }
+
+    pub fn overlaps(&self, other: &Self) -> bool {
+        self.min.cmple(other.max).all() && other.min.cmple(self.max).all()
+    }
+}
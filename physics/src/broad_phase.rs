@@ -0,0 +1,134 @@
+use common::Entity;
+use indexmap::IndexMap;
+
+use glam::Vec3;
+
+use crate::{BodyType, RigidBody, aabb::Aabb};
+
+// Bounding boxes are grown by this much so resting contacts stay paired
+const AABB_MARGIN: f32 = 0.01;
+// How much more spread another axis needs before sorting switches to it.
+// Every switch scrambles the sorted order, so flip-flopping between two
+// similar axes would make each step's sort quadratic.
+const AXIS_SWITCH_RATIO: f32 = 1.5;
+
+// Sweep and prune over the axis with the most spread. The sorted order is kept
+// between steps, so re-sorting after small movements is close to linear.
+// Bodies are bounded by everywhere they can reach within the step, so the
+// pairs found once can be used for the whole step.
+pub(crate) struct SweepAndPrune {
+    order: Vec<usize>,
+    axis: usize,
+    // Step bounds of each body with the bounds of the body they were made
+    // from. Static and sleeping bodies keep theirs until they get moved.
+    bounds: Vec<(Aabb, Aabb)>,
+    // dt and gravity the bounds were made with
+    step_params: (f32, Vec3),
+}
+
+impl SweepAndPrune {
+    pub fn new() -> Self {
+        Self {
+            order: vec![],
+            axis: 0,
+            bounds: vec![],
+            step_params: (0.0, Vec3::ZERO),
+        }
+    }
+
+    fn update_bounds(
+        &mut self,
+        rigid_bodies: &IndexMap<Entity, RigidBody>,
+        dt: f32,
+        gravity: Vec3,
+    ) {
+        if self.step_params != (dt, gravity) || self.bounds.len() != rigid_bodies.len() {
+            self.step_params = (dt, gravity);
+            self.bounds.clear();
+        }
+        for (i, rb) in rigid_bodies.values().enumerate() {
+            let resting = rb.sleeping || rb.body_type == BodyType::Static;
+            if resting
+                && self
+                    .bounds
+                    .get(i)
+                    .is_some_and(|(aabb, _)| aabb == rb.aabb())
+            {
+                continue;
+            }
+            let entry = (
+                *rb.aabb(),
+                rb.step_bounds(dt, gravity).expanded(AABB_MARGIN),
+            );
+            match self.bounds.get_mut(i) {
+                Some(cached) => *cached = entry,
+                None => self.bounds.push(entry),
+            }
+        }
+    }
+
+    fn pick_axis(&mut self, bounds: &[Aabb]) {
+        let count = bounds.len() as f32;
+        let mut sum = glam::Vec3::ZERO;
+        let mut sum_sq = glam::Vec3::ZERO;
+        for aabb in bounds {
+            let c = aabb.center();
+            sum += c;
+            sum_sq += c * c;
+        }
+        let variance = sum_sq / count - (sum / count) * (sum / count);
+        let best = if variance.x >= variance.y && variance.x >= variance.z {
+            0
+        } else if variance.y >= variance.z {
+            1
+        } else {
+            2
+        };
+        if variance[best] > variance[self.axis] * AXIS_SWITCH_RATIO {
+            self.axis = best;
+        }
+    }
+
+    // Returns index pairs (i < j) whose step bounds overlap, in sorted order
+    pub fn update(
+        &mut self,
+        rigid_bodies: &IndexMap<Entity, RigidBody>,
+        dt: f32,
+        gravity: Vec3,
+    ) -> Vec<(usize, usize)> {
+        if self.order.len() != rigid_bodies.len() {
+            self.order = (0..rigid_bodies.len()).collect();
+        }
+        if rigid_bodies.is_empty() {
+            return vec![];
+        }
+        self.update_bounds(rigid_bodies, dt, gravity);
+        let bounds: Vec<_> = self.bounds.iter().map(|(_, bounds)| *bounds).collect();
+        self.pick_axis(&bounds);
+        let axis = self.axis;
+        let min_of = |i: usize| bounds[i].min[axis];
+        // Insertion sort, cheap since the order barely changes between steps
+        for k in 1..self.order.len() {
+            let mut m = k;
+            while m > 0 && min_of(self.order[m - 1]) > min_of(self.order[m]) {
+                self.order.swap(m - 1, m);
+                m -= 1;
+            }
+        }
+        let mut pairs = vec![];
+        for (k, &i) in self.order.iter().enumerate() {
+            let aabb_i = bounds[i];
+            for &j in &self.order[k + 1..] {
+                let aabb_j = bounds[j];
+                if aabb_j.min[axis] > aabb_i.max[axis] {
+                    break;
+                }
+                if aabb_i.overlaps(&aabb_j) {
+                    pairs.push((i.min(j), i.max(j)));
+                }
+            }
+        }
+        pairs.sort_unstable();
+        pairs
+    }
+}
@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+
+use crate::aabb::Aabb;
+
+const MAX_LEAF_ITEMS: usize = 4;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum BvhNode {
+    Leaf {
+        aabb: Aabb,
+        start: usize,
+        count: usize,
+    },
+    Branch {
+        aabb: Aabb,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl BvhNode {
+    fn aabb(&self) -> &Aabb {
+        match self {
+            Self::Leaf { aabb, .. } | Self::Branch { aabb, .. } => aabb,
+        }
+    }
+}
+
+// Bounding volume hierarchy over a fixed set of boxes, split at the median
+// of the longest axis
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Bvh {
+    nodes: Vec<BvhNode>,
+    items: Vec<usize>,
+    item_aabbs: Vec<Aabb>,
+}
+
+impl Bvh {
+    pub fn new(aabbs: &[Aabb]) -> Self {
+        let mut bvh = Self {
+            nodes: vec![],
+            items: (0..aabbs.len()).collect(),
+            item_aabbs: aabbs.to_vec(),
+        };
+        if !aabbs.is_empty() {
+            bvh.build(aabbs, 0, aabbs.len());
+        }
+        bvh
+    }
+
+    fn build(&mut self, aabbs: &[Aabb], start: usize, end: usize) -> usize {
+        let items = &mut self.items[start..end];
+        let aabb = items
+            .iter()
+            .map(|&i| aabbs[i])
+            .reduce(|a, b| a.union(&b))
+            .unwrap_or(aabbs[0]);
+        let node_idx = self.nodes.len();
+        if items.len() <= MAX_LEAF_ITEMS {
+            self.nodes.push(BvhNode::Leaf {
+                aabb,
+                start,
+                count: items.len(),
+            });
+            return node_idx;
+        }
+        let extent = aabb.max - aabb.min;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        };
+        let mid = items.len() / 2;
+        items.select_nth_unstable_by(mid, |&a, &b| {
+            aabbs[a].center()[axis].total_cmp(&aabbs[b].center()[axis])
+        });
+        // Reserve the slot so children land after their parent
+        self.nodes.push(BvhNode::Leaf {
+            aabb,
+            start,
+            count: 0,
+        });
+        let left = self.build(aabbs, start, start + mid);
+        let right = self.build(aabbs, start + mid, end);
+        self.nodes[node_idx] = BvhNode::Branch { aabb, left, right };
+        node_idx
+    }
+
+    pub fn aabb(&self) -> Option<&Aabb> {
+        self.nodes.first().map(|n| n.aabb())
+    }
+
+    // Items whose boxes overlap the query box
+    pub fn query(&self, query: &Aabb) -> Vec<usize> {
+        let mut out = vec![];
+        let mut stack = vec![];
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+        while let Some(node_idx) = stack.pop() {
+            let node = &self.nodes[node_idx];
+            if !node.aabb().overlaps(query) {
+                continue;
+            }
+            match node {
+                BvhNode::Leaf { start, count, .. } => out.extend(
+                    self.items[*start..start + count]
+                        .iter()
+                        .filter(|&&i| self.item_aabbs[i].overlaps(query)),
+                ),
+                BvhNode::Branch { left, right, .. } => {
+                    stack.push(*left);
+                    stack.push(*right);
+                }
+            }
+        }
+        out
+    }
+}
@@ -0,0 +1,271 @@
+use glam::Vec3;
+
+use crate::{
+    CollisionFilter, Orientation, PhysicsManager, collision_shape::CollisionShape,
+    intersection_info::IntersectionInfo,
+};
+
+// Gap kept between the capsule and whatever it walks against, so casts don't
+// start out touching
+const SKIN_WIDTH: f32 = 0.01;
+const MAX_SLIDES: usize = 4;
+const MAX_DEPENETRATION_PASSES: usize = 4;
+// How far past an edge the surface under it is probed
+const EDGE_PROBE: f32 = 0.02;
+
+// Upright capsule moved by casting it through the world instead of by forces.
+// Rigid bodies push it out but it doesn't push them.
+#[derive(Debug, Clone)]
+pub struct CharacterController {
+    // Center of the capsule
+    pub position: Vec3,
+    pub radius: f32,
+    // Half the length of the capsule's inner segment
+    pub half_height: f32,
+    pub step_height: f32,
+    // Steepest slope in radians that can be walked up and stood on
+    pub max_slope: f32,
+    pub jump_speed: f32,
+    pub filter: CollisionFilter,
+    pub velocity: Vec3,
+    on_ground: bool,
+}
+
+impl CharacterController {
+    pub fn new(position: Vec3, radius: f32, half_height: f32) -> Self {
+        Self {
+            position,
+            radius,
+            half_height,
+            step_height: 0.3,
+            max_slope: 45f32.to_radians(),
+            jump_speed: 5.0,
+            filter: CollisionFilter::default(),
+            velocity: Vec3::ZERO,
+            on_ground: false,
+        }
+    }
+
+    pub fn is_on_ground(&self) -> bool {
+        self.on_ground
+    }
+
+    pub fn jump(&mut self) {
+        if self.on_ground {
+            self.velocity.y = self.jump_speed;
+            self.on_ground = false;
+        }
+    }
+
+    // Lowest point of the capsule
+    pub fn feet(&self) -> Vec3 {
+        self.position - Vec3::Y * (self.half_height + self.radius)
+    }
+
+    fn shape_at(&self, position: Vec3) -> CollisionShape {
+        let half = Vec3::Y * self.half_height;
+        CollisionShape::new_capsule(position - half, position + half, self.radius)
+    }
+
+    fn is_walkable(&self, normal: Vec3) -> bool {
+        normal.y >= self.max_slope.cos()
+    }
+}
+
+impl PhysicsManager {
+    // Moves the character by walk_velocity along the ground plus its own
+    // vertical velocity, sliding along walls and stepping over small ledges
+    pub fn move_character(
+        &self,
+        character: &mut CharacterController,
+        walk_velocity: Vec3,
+        dt: f32,
+    ) {
+        self.depenetrate_character(character);
+        let start = character.position;
+
+        let walk = Vec3::new(walk_velocity.x, 0.0, walk_velocity.z) * dt;
+        let mut position = self.slide_character(character, start, walk);
+        if character.on_ground && character.step_height > 0.0 && walk != Vec3::ZERO {
+            // Try the same move from higher up and keep it if it got further
+            let raised = self.slide_character(character, start, Vec3::Y * character.step_height);
+            let moved = self.slide_character(character, raised, walk);
+            let highest = character.feet().y + character.step_height;
+            if let Some(stepped) =
+                self.snap_to_ground(character, moved, raised.y - start.y, highest)
+            {
+                let flat_dist = (position - start).with_y(0.0).length_squared();
+                let step_dist = (stepped - start).with_y(0.0).length_squared();
+                if step_dist > flat_dist + SKIN_WIDTH * SKIN_WIDTH {
+                    position = stepped;
+                }
+            }
+        }
+
+        if !character.on_ground {
+            character.velocity += self.config.gravity * dt;
+        }
+        let fall = Vec3::Y * character.velocity.y * dt;
+        let before_fall = position;
+        position = self.slide_character(character, position, fall);
+        if fall.y > 0.0 && position.y - before_fall.y < fall.y * 0.5 {
+            // Bumped the head
+            character.velocity.y = 0.0;
+        }
+        if fall.y < 0.0 && position.y - before_fall.y > fall.y * 0.5 {
+            // Held up by something, don't keep gathering speed against it
+            character.velocity.y = 0.0;
+        }
+        if character.on_ground && character.velocity.y <= 0.0 {
+            // Stick to the ground walking down slopes and stairs
+            if let Some(snapped) =
+                self.snap_to_ground(character, position, character.step_height, f32::INFINITY)
+            {
+                position = snapped;
+            }
+        }
+        character.position = position;
+
+        let ground = self.cast_ground(character, position, SKIN_WIDTH * 2.0);
+        character.on_ground = ground.is_some_and(|(_, normal, _)| character.is_walkable(normal));
+        if character.on_ground && character.velocity.y < 0.0 {
+            character.velocity.y = 0.0;
+        }
+        if let Some((_, normal, _)) = ground
+            && !character.on_ground
+            && normal.y > 0.0
+        {
+            // Resting on an edge or a slope too steep to stand on. Casts from
+            // there start out touching and can't move the capsule, so ease it
+            // off directly and let it slide down from there.
+            character.position += normal.with_y(0.0).normalize_or_zero() * SKIN_WIDTH;
+        }
+    }
+
+    // Fraction of motion the capsule can make from position, and the normal of
+    // what it hits
+    fn cast_character(
+        &self,
+        character: &CharacterController,
+        position: Vec3,
+        motion: Vec3,
+    ) -> Option<(f32, Vec3)> {
+        let hit = self.shape_cast(
+            &character.shape_at(position),
+            &Orientation::new(),
+            motion,
+            motion.length(),
+            &character.filter,
+        )?;
+        Some((hit.fraction, hit.normal))
+    }
+
+    // Casts the capsule straight down by up to depth. Gives the fraction, and
+    // the normal and a point of the surface it lands on rather than of the
+    // contact, which for the rounded bottom resting on an edge leans away from
+    // the ledge the capsule is actually standing on.
+    fn cast_ground(
+        &self,
+        character: &CharacterController,
+        position: Vec3,
+        depth: f32,
+    ) -> Option<(f32, Vec3, Vec3)> {
+        let hit = self.shape_cast(
+            &character.shape_at(position),
+            &Orientation::new(),
+            -Vec3::Y,
+            depth,
+            &character.filter,
+        )?;
+        let outward = hit.normal.with_y(0.0).normalize_or_zero();
+        if character.is_walkable(hit.normal) || hit.normal.y <= 0.0 || outward == Vec3::ZERO {
+            return Some((hit.fraction, hit.normal, hit.point));
+        }
+        // Start high enough to stay above a plain slope as steep as the contact
+        let rise = (EDGE_PROBE * hit.normal.with_y(0.0).length() / hit.normal.y + EDGE_PROBE)
+            .min(character.radius);
+        let probe = hit.point - outward * EDGE_PROBE + Vec3::Y * rise;
+        let (normal, point) = self
+            .raycast(probe, -Vec3::Y, rise + EDGE_PROBE, &character.filter)
+            .map_or((hit.normal, hit.point), |surface| {
+                (surface.normal, surface.point)
+            });
+        Some((hit.fraction, normal, point))
+    }
+
+    // Collide and slide: move until something is hit, then carry on with what's
+    // left of the motion along the surface
+    fn slide_character(
+        &self,
+        character: &CharacterController,
+        mut position: Vec3,
+        motion: Vec3,
+    ) -> Vec3 {
+        let mut remaining = motion;
+        for _ in 0..MAX_SLIDES {
+            let len = remaining.length();
+            if len <= f32::EPSILON {
+                break;
+            }
+            let Some((fraction, mut normal)) = self.cast_character(character, position, remaining)
+            else {
+                position += remaining;
+                break;
+            };
+            position += remaining / len * (fraction * len - SKIN_WIDTH).max(0.0);
+            // Steep slopes act as walls when walking, they can't be climbed
+            if motion.y == 0.0 && !character.is_walkable(normal) && normal.y > 0.0 {
+                normal = normal.with_y(0.0).normalize_or(normal);
+            }
+            remaining *= 1.0 - fraction;
+            remaining -= normal * remaining.dot(normal);
+        }
+        position
+    }
+
+    // Moves the character down onto walkable ground up to max_drop below,
+    // as long as that ground is no higher than highest
+    fn snap_to_ground(
+        &self,
+        character: &CharacterController,
+        position: Vec3,
+        max_drop: f32,
+        highest: f32,
+    ) -> Option<Vec3> {
+        let depth = max_drop + SKIN_WIDTH;
+        let (fraction, normal, point) = self.cast_ground(character, position, depth)?;
+        if !character.is_walkable(normal) || point.y > highest {
+            return None;
+        }
+        Some(position - Vec3::Y * (depth * fraction - SKIN_WIDTH))
+    }
+
+    // Pushes the capsule out of bodies it ended up inside, like ones that
+    // moved into it
+    fn depenetrate_character(&self, character: &mut CharacterController) {
+        for _ in 0..MAX_DEPENETRATION_PASSES {
+            let capsule = character.shape_at(character.position);
+            let aabb = capsule.aabb();
+            let deepest = self
+                .rigid_bodies
+                .values()
+                .filter(|rb| {
+                    !rb.is_sensor
+                        && character.filter.can_interact(&rb.filter)
+                        && aabb.overlaps(rb.aabb())
+                })
+                .flat_map(|rb| rb.orient_shape.parts_near(&aabb))
+                .filter_map(|(_, part)| {
+                    IntersectionInfo::new_with_gjk(&capsule, &part)
+                        .inspect_err(|e| log::warn!("character penetration test skipped: {e}"))
+                        .ok()
+                        .flatten()
+                })
+                .max_by(|x, y| x.dist.total_cmp(&y.dist));
+            let Some(deepest) = deepest else {
+                break;
+            };
+            character.position -= deepest.dir * (deepest.dist + SKIN_WIDTH);
+        }
+    }
+}
@@ -1,21 +1,42 @@
-use glam::{Vec3, Vec4Swizzles};
+use std::{borrow::Cow, f32::consts::PI, sync::Arc};
 
-use crate::{Orientation, utils::point_vec4};
+use glam::{Mat4, Vec3, Vec4Swizzles};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+use crate::{
+    Orientation,
+    aabb::Aabb,
+    bvh::Bvh,
+    convex_hull::ConvexHull,
+    utils::{point_vec4, polygon_normal},
+};
+
+// Faces closer than this to the query direction are used as a whole
+const FACE_ALIGN_THRESHOLD: f32 = 0.9;
+// Edges this close to perpendicular to the query direction are used as a whole
+const EDGE_ALIGN_THRESHOLD: f32 = 0.2;
+
+// Two convex parts that may touch, with their indices in convex_parts
+pub(crate) type PartPair<'a> = (
+    (usize, usize),
+    Cow<'a, CollisionShape>,
+    Cow<'a, CollisionShape>,
+);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Sphere {
     pub(crate) center: Vec3,
     pub(crate) radius: f32,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Capsule {
     pub(crate) a: Vec3,
     pub(crate) b: Vec3,
     pub(crate) radius: f32,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Mesh {
     pub(crate) center: Vec3,
     pub(crate) points: Vec<Vec3>,
@@ -23,11 +44,206 @@ pub struct Mesh {
     pub(crate) faces: Vec<Vec<u32>>,
 }
 
+// Convex parts already placed in the compound's frame
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Compound {
+    pub(crate) center: Vec3,
+    pub(crate) children: Vec<CollisionShape>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct TriangleMeshData {
+    pub(crate) center: Vec3,
+    pub(crate) points: Vec<Vec3>,
+    pub(crate) triangles: Vec<CollisionShape>,
+    pub(crate) bvh: Bvh,
+}
+
+// Non-convex triangle soup for static level geometry. Collides one triangle
+// at a time. The triangles and their BVH stay in the mesh's own frame and are
+// shared by every orientation of it, queries get moved into that frame
+// instead.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TriangleMesh {
+    pub(crate) data: Arc<TriangleMeshData>,
+    pub(crate) orient: Orientation,
+}
+
+impl TriangleMesh {
+    fn new(points: Vec<Vec3>, indices: &[[u32; 3]]) -> Self {
+        let triangles: Vec<CollisionShape> = indices
+            .iter()
+            .map(|tri| {
+                let [a, b, c] = tri.map(|i| points[i as usize]);
+                CollisionShape::new_triangle(a, b, c)
+            })
+            .collect();
+        let aabbs: Vec<Aabb> = triangles.iter().map(|t| t.aabb()).collect();
+        let data = TriangleMeshData {
+            center: points.iter().sum::<Vec3>() / points.len().max(1) as f32,
+            bvh: Bvh::new(&aabbs),
+            points,
+            triangles,
+        };
+        Self {
+            data: Arc::new(data),
+            orient: Orientation::new(),
+        }
+    }
+
+    pub(crate) fn center(&self) -> Vec3 {
+        self.orient
+            .to_transform()
+            .transform_point3(self.data.center)
+    }
+
+    pub(crate) fn triangle_count(&self) -> usize {
+        self.data.triangles.len()
+    }
+
+    pub(crate) fn triangle(&self, i: usize) -> CollisionShape {
+        self.data.triangles[i].with_orientation(&self.orient)
+    }
+
+    // Triangles whose bounds overlap aabb, given in world space
+    pub(crate) fn query(&self, aabb: &Aabb) -> Vec<usize> {
+        self.data.bvh.query(&transformed_aabb(
+            aabb,
+            &self.orient.to_transform().inverse(),
+        ))
+    }
+
+    pub(crate) fn aabb(&self) -> Aabb {
+        match self.data.bvh.aabb() {
+            Some(aabb) => transformed_aabb(aabb, &self.orient.to_transform()),
+            None => Aabb::new(self.center(), self.center()),
+        }
+    }
+
+    pub(crate) fn farthest_point_along(&self, dir: Vec3) -> Vec3 {
+        let local_dir = self.orient.rotation.transpose().transform_vector3(dir);
+        let local = self
+            .data
+            .points
+            .iter()
+            .copied()
+            .max_by(|p, q| p.dot(local_dir).total_cmp(&q.dot(local_dir)))
+            .unwrap_or(self.data.center);
+        self.orient.to_transform().transform_point3(local)
+    }
+}
+
+// Box around aabb after moving it by transform
+fn transformed_aabb(aabb: &Aabb, transform: &Mat4) -> Aabb {
+    let corners: Vec<Vec3> = (0..8)
+        .map(|k| {
+            let pick = |bit: usize, axis: usize| {
+                if k & bit == 0 {
+                    aabb.min[axis]
+                } else {
+                    aabb.max[axis]
+                }
+            };
+            transform.transform_point3(Vec3::new(pick(1, 0), pick(2, 1), pick(4, 2)))
+        })
+        .collect();
+    Aabb::from_points(&corners)
+}
+
+// Part of a shape's surface that is extreme along some direction
 #[derive(Debug, Clone)]
+pub(crate) enum SupportFeature {
+    Point(Vec3),
+    Edge(Vec3, Vec3),
+    Face { points: Vec<Vec3>, normal: Vec3 },
+}
+
+impl SupportFeature {
+    pub fn points(&self) -> Vec<Vec3> {
+        match self {
+            Self::Point(p) => vec![*p],
+            Self::Edge(p, q) => vec![*p, *q],
+            Self::Face { points, .. } => points.clone(),
+        }
+    }
+}
+
+impl Mesh {
+    pub fn points(&self) -> &[Vec3] {
+        &self.points
+    }
+
+    fn face_points(&self, face: &[u32]) -> Vec<Vec3> {
+        face.iter().map(|&i| self.points[i as usize]).collect()
+    }
+
+    fn support_feature(&self, dir: Vec3) -> SupportFeature {
+        let mut best_face = None;
+        let mut best_align = FACE_ALIGN_THRESHOLD;
+        for face in &self.faces {
+            let points = self.face_points(face);
+            let normal = polygon_normal(&points);
+            let align = normal.dot(dir);
+            if align >= best_align {
+                best_align = align;
+                best_face = Some((points, normal));
+            }
+        }
+        if let Some((points, normal)) = best_face {
+            return SupportFeature::Face { points, normal };
+        }
+        let mut max_idx = 0;
+        for (i, p) in self.points.iter().enumerate() {
+            if p.dot(dir) > self.points[max_idx].dot(dir) {
+                max_idx = i;
+            }
+        }
+        let support = self.points[max_idx];
+        let mut best_edge = None;
+        let mut best_align = EDGE_ALIGN_THRESHOLD;
+        for edge in &self.edges {
+            let other = if edge[0] as usize == max_idx {
+                edge[1]
+            } else if edge[1] as usize == max_idx {
+                edge[0]
+            } else {
+                continue;
+            };
+            let other = self.points[other as usize];
+            let align = (other - support).normalize_or_zero().dot(dir).abs();
+            if align <= best_align {
+                best_align = align;
+                best_edge = Some(other);
+            }
+        }
+        match best_edge {
+            Some(other) => SupportFeature::Edge(support, other),
+            None => SupportFeature::Point(support),
+        }
+    }
+}
+
+impl Compound {
+    // Weight of each child in the compound center, by volume. Falls back to an even
+    // split when the children are all flat.
+    fn child_weights(&self) -> Vec<f32> {
+        let volumes: Vec<f32> = self.children.iter().map(|c| c.volume()).collect();
+        let total: f32 = volumes.iter().sum();
+        if total > f32::EPSILON {
+            volumes.iter().map(|v| v / total).collect()
+        } else {
+            vec![1.0 / self.children.len() as f32; self.children.len()]
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum CollisionShape {
     Sphere(Sphere),
     Capsule(Capsule),
     Mesh(Mesh),
+    Compound(Compound),
+    TriangleMesh(TriangleMesh),
 }
 
 impl CollisionShape {
@@ -36,6 +252,97 @@ impl CollisionShape {
             Self::Sphere(sphere) => sphere.center,
             Self::Capsule(capsule) => (capsule.a + capsule.b) / 2.0,
             Self::Mesh(mesh) => mesh.center,
+            Self::Compound(compound) => compound.center,
+            Self::TriangleMesh(tri_mesh) => tri_mesh.center(),
+        }
+    }
+
+    pub fn volume(&self) -> f32 {
+        match self {
+            Self::Sphere(sphere) => 4.0 / 3.0 * PI * sphere.radius.powi(3),
+            Self::Capsule(capsule) => {
+                let r = capsule.radius;
+                PI * r * r * (capsule.b - capsule.a).length() + 4.0 / 3.0 * PI * r * r * r
+            }
+            Self::Mesh(mesh) => {
+                let mut vol = 0.0;
+                for face in &mesh.faces {
+                    let p0 = mesh.points[face[0] as usize] - mesh.center;
+                    for w in face[1..].windows(2) {
+                        let p1 = mesh.points[w[0] as usize] - mesh.center;
+                        let p2 = mesh.points[w[1] as usize] - mesh.center;
+                        vol += p0.dot(p1.cross(p2)) / 6.0;
+                    }
+                }
+                vol.abs()
+            }
+            Self::Compound(compound) => compound.children.iter().map(|c| c.volume()).sum(),
+            // Open surface, encloses nothing
+            Self::TriangleMesh(_) => 0.0,
+        }
+    }
+
+    // Convex pieces collision detection runs on. Triangle mesh pieces are
+    // built on the fly, prefer parts_near when only some are needed.
+    pub fn convex_parts(&self) -> Vec<Cow<'_, CollisionShape>> {
+        match self {
+            Self::Compound(compound) => compound.children.iter().map(Cow::Borrowed).collect(),
+            Self::TriangleMesh(tri_mesh) => (0..tri_mesh.triangle_count())
+                .map(|i| Cow::Owned(tri_mesh.triangle(i)))
+                .collect(),
+            _ => vec![Cow::Borrowed(self)],
+        }
+    }
+
+    // Convex parts whose bounds overlap aabb, along with their index in
+    // convex_parts. Convex shapes always return themselves.
+    pub(crate) fn parts_near(&self, aabb: &Aabb) -> Vec<(usize, Cow<'_, CollisionShape>)> {
+        match self {
+            Self::Compound(compound) => compound
+                .children
+                .iter()
+                .enumerate()
+                .filter(|(_, c)| c.aabb().overlaps(aabb))
+                .map(|(i, c)| (i, Cow::Borrowed(c)))
+                .collect(),
+            Self::TriangleMesh(tri_mesh) => tri_mesh
+                .query(aabb)
+                .into_iter()
+                .map(|i| (i, Cow::Owned(tri_mesh.triangle(i))))
+                .filter(|(_, t): &(usize, Cow<Self>)| t.aabb().overlaps(aabb))
+                .collect(),
+            _ => vec![(0, Cow::Borrowed(self))],
+        }
+    }
+
+    // Pairs of convex parts from both shapes that may touch
+    pub(crate) fn part_pairs<'a>(&'a self, other: &'a Self) -> Vec<PartPair<'a>> {
+        let mut pairs = vec![];
+        for (i, part_a) in self.parts_near(&other.aabb()) {
+            for (j, part_b) in other.parts_near(&part_a.aabb()) {
+                pairs.push(((i, j), part_a.clone(), part_b));
+            }
+        }
+        pairs
+    }
+
+    pub fn aabb(&self) -> Aabb {
+        match self {
+            Self::Sphere(sphere) => Aabb::new(
+                sphere.center - Vec3::splat(sphere.radius),
+                sphere.center + Vec3::splat(sphere.radius),
+            ),
+            Self::Capsule(capsule) => {
+                Aabb::from_points(&[capsule.a, capsule.b]).expanded(capsule.radius)
+            }
+            Self::Mesh(mesh) => Aabb::from_points(&mesh.points),
+            Self::Compound(compound) => compound
+                .children
+                .iter()
+                .map(|c| c.aabb())
+                .reduce(|a, b| a.union(&b))
+                .unwrap_or(Aabb::new(compound.center, compound.center)),
+            Self::TriangleMesh(tri_mesh) => tri_mesh.aabb(),
         }
     }
 
@@ -48,7 +355,7 @@ impl CollisionShape {
             }),
             Self::Capsule(capsule) => Self::Capsule(Capsule {
                 a: (tr * point_vec4(capsule.a)).xyz(),
-                b: (tr * point_vec4(capsule.a)).xyz(),
+                b: (tr * point_vec4(capsule.b)).xyz(),
                 radius: capsule.radius,
             }),
             Self::Mesh(mesh) => {
@@ -67,16 +374,37 @@ impl CollisionShape {
                     faces: mesh.faces.clone(),
                 })
             }
+            Self::Compound(compound) => Self::Compound(Compound {
+                center: (tr * point_vec4(compound.center)).xyz(),
+                children: compound
+                    .children
+                    .iter()
+                    .map(|c| c.with_orientation(orientation))
+                    .collect(),
+            }),
+            Self::TriangleMesh(tri_mesh) => Self::TriangleMesh(TriangleMesh {
+                data: tri_mesh.data.clone(),
+                orient: Orientation {
+                    translation: orientation
+                        .rotation
+                        .transform_point3(tri_mesh.orient.translation)
+                        + orientation.translation,
+                    rotation: orientation.rotation * tri_mesh.orient.rotation,
+                },
+            }),
         }
     }
 
     pub fn farthest_point_along(&self, dir: Vec3) -> Vec3 {
         match self {
-            Self::Sphere(sphere) => sphere.center + (dir * sphere.radius),
+            Self::Sphere(sphere) => sphere.center + (dir.normalize_or_zero() * sphere.radius),
             Self::Capsule(capsule) => {
-                let a_dist = capsule.a + (dir * capsule.radius);
-                let b_dist = capsule.b + (dir * capsule.radius);
-                a_dist.max(b_dist)
+                let offset = dir.normalize_or_zero() * capsule.radius;
+                if capsule.a.dot(dir) > capsule.b.dot(dir) {
+                    capsule.a + offset
+                } else {
+                    capsule.b + offset
+                }
             }
             Self::Mesh(mesh) => {
                 let mut max_dist = mesh.points[0].dot(dir);
@@ -90,7 +418,122 @@ impl CollisionShape {
                 }
                 max_dist_point
             }
+            // Support of the hull around all the children
+            Self::Compound(compound) => compound
+                .children
+                .iter()
+                .map(|c| c.farthest_point_along(dir))
+                .max_by(|p, q| p.dot(dir).total_cmp(&q.dot(dir)))
+                .unwrap_or(compound.center),
+            Self::TriangleMesh(tri_mesh) => tri_mesh.farthest_point_along(dir),
+        }
+    }
+
+    pub(crate) fn support_feature(&self, dir: Vec3) -> SupportFeature {
+        match self {
+            Self::Sphere(sphere) => SupportFeature::Point(sphere.center + dir * sphere.radius),
+            Self::Capsule(capsule) => {
+                let axis = (capsule.b - capsule.a).normalize_or_zero();
+                let offset = dir * capsule.radius;
+                if axis.dot(dir).abs() <= EDGE_ALIGN_THRESHOLD {
+                    SupportFeature::Edge(capsule.a + offset, capsule.b + offset)
+                } else if capsule.a.dot(dir) > capsule.b.dot(dir) {
+                    SupportFeature::Point(capsule.a + offset)
+                } else {
+                    SupportFeature::Point(capsule.b + offset)
+                }
+            }
+            Self::Mesh(mesh) => mesh.support_feature(dir),
+            Self::Compound(compound) => {
+                let support = self.farthest_point_along(dir);
+                compound
+                    .children
+                    .iter()
+                    .find(|c| c.farthest_point_along(dir) == support)
+                    .map(|c| c.support_feature(dir))
+                    .unwrap_or(SupportFeature::Point(support))
+            }
+            Self::TriangleMesh(_) => SupportFeature::Point(self.farthest_point_along(dir)),
+        }
+    }
+
+    pub fn new_sphere(c: Vec3, r: f32) -> Self {
+        Self::Sphere(Sphere {
+            center: c,
+            radius: r,
+        })
+    }
+
+    pub fn new_capsule(a: Vec3, b: Vec3, r: f32) -> Self {
+        Self::Capsule(Capsule { a, b, radius: r })
+    }
+
+    // Convex mesh around a point cloud, optionally reduced to max_vertices
+    pub fn new_convex_hull(points: &[Vec3], max_vertices: Option<usize>) -> anyhow::Result<Self> {
+        let hull = ConvexHull::new(points, max_vertices)?;
+        let center = hull.points.iter().sum::<Vec3>() / hull.points.len() as f32;
+        Ok(Self::Mesh(Mesh {
+            center,
+            points: hull.points,
+            edges: hull.edges,
+            faces: hull.faces,
+        }))
+    }
+
+    // Children are placed by their local orientations. Nested compounds get
+    // flattened into this one. Parts have to be convex, so triangle meshes
+    // can't be one.
+    pub fn new_compound(children: Vec<(Orientation, CollisionShape)>) -> anyhow::Result<Self> {
+        let mut parts = vec![];
+        for (orient, child) in children {
+            match child.with_orientation(&orient) {
+                Self::Compound(compound) => parts.extend(compound.children),
+                Self::TriangleMesh(_) => anyhow::bail!("compound part can't be a triangle mesh"),
+                part => parts.push(part),
+            }
         }
+        let mut compound = Compound {
+            center: Vec3::ZERO,
+            children: parts,
+        };
+        compound.center = compound
+            .children
+            .iter()
+            .zip(compound.child_weights())
+            .map(|(c, w)| c.center_hint() * w)
+            .sum();
+        Ok(Self::Compound(compound))
+    }
+
+    // Static collider from render mesh style data, three indices per triangle
+    pub fn new_triangle_mesh(vertices: &[Vec3], indices: &[u32]) -> anyhow::Result<Self> {
+        if !indices.len().is_multiple_of(3) {
+            anyhow::bail!(
+                "triangle mesh index count {} isn't a multiple of 3",
+                indices.len()
+            );
+        }
+        if let Some(i) = indices.iter().find(|&&i| i as usize >= vertices.len()) {
+            anyhow::bail!("triangle mesh index {i} out of {} vertices", vertices.len());
+        }
+        let indices: Vec<_> = indices
+            .chunks_exact(3)
+            .map(|tri| [tri[0], tri[1], tri[2]])
+            .collect();
+        Ok(Self::TriangleMesh(TriangleMesh::new(
+            vertices.to_vec(),
+            &indices,
+        )))
+    }
+
+    pub fn new_triangle(a: Vec3, b: Vec3, c: Vec3) -> Self {
+        let mesh = Mesh {
+            center: (a + b + c) / 3.0,
+            points: vec![a, b, c],
+            edges: vec![[0, 1], [1, 2], [2, 0]],
+            faces: vec![vec![0, 1, 2], vec![0, 2, 1]],
+        };
+        Self::Mesh(mesh)
     }
 
     pub fn new_rect(c: Vec3, u: Vec3, v: Vec3) -> Self {
@@ -0,0 +1,195 @@
+use glam::Vec3;
+
+use crate::{
+    collision_shape::{CollisionShape, SupportFeature},
+    error::CollisionError,
+    intersection_info::IntersectionInfo,
+    utils::closest_points_on_segments,
+};
+
+const MAX_MANIFOLD_POINTS: usize = 4;
+// Clipped points this far outside the reference face still count as touching
+const CONTACT_SLOP: f32 = 0.005;
+
+#[derive(Debug, Clone)]
+pub struct ContactPoint {
+    pub point: Vec3,
+    pub depth: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct ContactManifold {
+    pub normal: Vec3,
+    pub points: Vec<ContactPoint>,
+}
+
+impl ContactManifold {
+    pub fn new_with_gjk(
+        a: &CollisionShape,
+        b: &CollisionShape,
+    ) -> Result<Option<Self>, CollisionError> {
+        let info = IntersectionInfo::new_with_gjk(a, b)?;
+        Ok(info.map(|info| Self::from_intersection(a, b, &info)))
+    }
+
+    pub fn new_with_gjk_from(
+        a: &CollisionShape,
+        b: &CollisionShape,
+        dir: &mut Vec3,
+    ) -> Result<Option<Self>, CollisionError> {
+        let info = IntersectionInfo::new_with_gjk_from(a, b, dir)?;
+        Ok(info.map(|info| Self::from_intersection(a, b, &info)))
+    }
+
+    pub fn from_intersection(
+        a: &CollisionShape,
+        b: &CollisionShape,
+        info: &IntersectionInfo,
+    ) -> Self {
+        let normal = info.dir;
+        let feat_a = a.support_feature(normal);
+        let feat_b = b.support_feature(-normal);
+        let mut points = match (&feat_a, &feat_b) {
+            (
+                SupportFeature::Face {
+                    points: face_a,
+                    normal: n_a,
+                },
+                SupportFeature::Face {
+                    points: face_b,
+                    normal: n_b,
+                },
+            ) => {
+                if n_a.dot(normal) >= -n_b.dot(normal) {
+                    clip_against_face(face_a, *n_a, &feat_b.points())
+                } else {
+                    clip_against_face(face_b, *n_b, &feat_a.points())
+                }
+            }
+            (SupportFeature::Face { points, normal }, other)
+            | (other, SupportFeature::Face { points, normal }) => {
+                clip_against_face(points, *normal, &other.points())
+            }
+            _ => vec![],
+        };
+        if points.is_empty() {
+            points.push(closest_feature_point(&feat_a, &feat_b, info.dist));
+        }
+        reduce_points(&mut points, normal);
+        Self { normal, points }
+    }
+
+    pub fn obj_swapped(mut self) -> Self {
+        self.normal = -self.normal;
+        self
+    }
+}
+
+fn feature_segment(feature: &SupportFeature) -> (Vec3, Vec3) {
+    match feature {
+        SupportFeature::Point(p) => (*p, *p),
+        SupportFeature::Edge(p, q) => (*p, *q),
+        SupportFeature::Face { points, .. } => (points[0], points[0]),
+    }
+}
+
+fn closest_feature_point(a: &SupportFeature, b: &SupportFeature, depth: f32) -> ContactPoint {
+    let (p1, q1) = feature_segment(a);
+    let (p2, q2) = feature_segment(b);
+    let (on_a, on_b) = closest_points_on_segments(p1, q1, p2, q2);
+    ContactPoint {
+        point: (on_a + on_b) / 2.0,
+        depth,
+    }
+}
+
+// Sutherland-Hodgman clip of the incident polygon against the side planes of
+// the reference face, keeping only the points that are below the face.
+fn clip_against_face(face: &[Vec3], face_normal: Vec3, incident: &[Vec3]) -> Vec<ContactPoint> {
+    let mut clipped = incident.to_vec();
+    if clipped.len() > 1 {
+        for (i, &v0) in face.iter().enumerate() {
+            let v1 = face[(i + 1) % face.len()];
+            let inward = face_normal.cross(v1 - v0);
+            if inward.length_squared() == 0.0 {
+                continue;
+            }
+            clipped = clip_polygon(&clipped, inward, v0);
+            if clipped.is_empty() {
+                break;
+            }
+        }
+    }
+    clipped
+        .into_iter()
+        .filter_map(|p| {
+            let depth = -(p - face[0]).dot(face_normal);
+            if depth < -CONTACT_SLOP {
+                return None;
+            }
+            Some(ContactPoint {
+                point: p + face_normal * depth / 2.0,
+                depth: depth.max(0.0),
+            })
+        })
+        .collect()
+}
+
+fn clip_polygon(points: &[Vec3], plane_n: Vec3, plane_p: Vec3) -> Vec<Vec3> {
+    let dist = |p: Vec3| (p - plane_p).dot(plane_n);
+    if points.len() == 2 {
+        let (d0, d1) = (dist(points[0]), dist(points[1]));
+        return match (d0 >= 0.0, d1 >= 0.0) {
+            (true, true) => points.to_vec(),
+            (false, false) => vec![],
+            _ => {
+                let cut = points[0] + (points[1] - points[0]) * (d0 / (d0 - d1));
+                if d0 >= 0.0 {
+                    vec![points[0], cut]
+                } else {
+                    vec![cut, points[1]]
+                }
+            }
+        };
+    }
+    let mut out = vec![];
+    for (i, &p) in points.iter().enumerate() {
+        let q = points[(i + 1) % points.len()];
+        let (dp, dq) = (dist(p), dist(q));
+        if dp >= 0.0 {
+            out.push(p);
+        }
+        if (dp >= 0.0) != (dq >= 0.0) {
+            out.push(p + (q - p) * (dp / (dp - dq)));
+        }
+    }
+    out
+}
+
+// Keep the deepest point and the ones that span the largest area around it
+fn reduce_points(points: &mut Vec<ContactPoint>, normal: Vec3) {
+    if points.len() <= MAX_MANIFOLD_POINTS {
+        return;
+    }
+    let max_by = |pts: &[ContactPoint], f: &dyn Fn(&ContactPoint) -> f32| {
+        let mut best = 0;
+        for (i, p) in pts.iter().enumerate() {
+            if f(p) > f(&pts[best]) {
+                best = i;
+            }
+        }
+        best
+    };
+    let first = points.swap_remove(max_by(points, &|p| p.depth));
+    let second = points.swap_remove(max_by(points, &|p| {
+        (p.point - first.point).length_squared()
+    }));
+    let signed_area = |p: &ContactPoint| {
+        (second.point - first.point)
+            .cross(p.point - first.point)
+            .dot(normal)
+    };
+    let third = points.swap_remove(max_by(points, &signed_area));
+    let fourth = points.swap_remove(max_by(points, &|p| -signed_area(p)));
+    *points = vec![first, second, third, fourth];
+}
@@ -0,0 +1,228 @@
+use common::Entity;
+use glam::Vec3;
+use indexmap::IndexMap;
+
+use crate::{
+    Material, RigidBody,
+    contact_manifold::ContactManifold,
+    joint::{Joint, JointConstraint},
+    pair_cache::{CachedContact, PairCache},
+};
+
+const SOLVER_ITERATIONS: usize = 10;
+// Approach speeds below this don't bounce, otherwise resting contacts jitter
+const RESTITUTION_THRESHOLD: f32 = 0.2;
+
+fn effective_mass(rb_a: &RigidBody, rb_b: &RigidBody, r_a: Vec3, r_b: Vec3, dir: Vec3) -> f32 {
+    let ang_a = (rb_a.inv_inertia_world() * r_a.cross(dir)).cross(r_a);
+    let ang_b = (rb_b.inv_inertia_world() * r_b.cross(dir)).cross(r_b);
+    let k = rb_a.inv_mass() + rb_b.inv_mass() + dir.dot(ang_a + ang_b);
+    if k > 0.0 { 1.0 / k } else { 0.0 }
+}
+
+pub(crate) struct ContactConstraint {
+    a: usize,
+    b: usize,
+    normal: Vec3,
+    // Contact point in a's local frame, to find it again next step
+    local_point: Vec3,
+    r_a: Vec3,
+    r_b: Vec3,
+    normal_mass: f32,
+    material: Material,
+    target_vel: f32,
+    normal_impulse: f32,
+    tangent_impulse: Vec3,
+}
+
+impl ContactConstraint {
+    fn new(
+        rigid_bodies: &IndexMap<Entity, RigidBody>,
+        a: usize,
+        b: usize,
+        normal: Vec3,
+        point: Vec3,
+        cache: Option<&PairCache>,
+    ) -> Self {
+        let rb_a = &rigid_bodies[a];
+        let rb_b = &rigid_bodies[b];
+        let local_point = rb_a.orient.to_transform().inverse().transform_point3(point);
+        let cached = cache.and_then(|cache| cache.find_contact(local_point));
+        let r_a = point - rb_a.center_of_mass();
+        let r_b = point - rb_b.center_of_mass();
+        let material = rb_a.material.combine(&rb_b.material);
+        let approach_vel = (rb_b.velocity_at(point) - rb_a.velocity_at(point)).dot(normal);
+        let target_vel = if approach_vel < -RESTITUTION_THRESHOLD {
+            -material.restitution * approach_vel
+        } else {
+            0.0
+        };
+        Self {
+            a,
+            b,
+            normal,
+            local_point,
+            r_a,
+            r_b,
+            normal_mass: effective_mass(rb_a, rb_b, r_a, r_b, normal),
+            material,
+            target_vel,
+            normal_impulse: cached.map_or(0.0, |c| c.normal_impulse),
+            // The normal may have turned a little since, keep what's still
+            // sideways
+            tangent_impulse: cached.map_or(Vec3::ZERO, |c| {
+                c.tangent_impulse.reject_from_normalized(normal)
+            }),
+        }
+    }
+
+    // Starts off from last step's impulses, resting contacts then only need
+    // small corrections
+    fn warm_start(&self, rigid_bodies: &mut IndexMap<Entity, RigidBody>) {
+        self.apply_impulse(
+            rigid_bodies,
+            self.normal * self.normal_impulse + self.tangent_impulse,
+        );
+    }
+
+    fn relative_velocity(&self, rigid_bodies: &IndexMap<Entity, RigidBody>) -> Vec3 {
+        let rb_a = &rigid_bodies[self.a];
+        let rb_b = &rigid_bodies[self.b];
+        (rb_b.kinematics.velocity + rb_b.kinematics.angular_velocity.cross(self.r_b))
+            - (rb_a.kinematics.velocity + rb_a.kinematics.angular_velocity.cross(self.r_a))
+    }
+
+    fn apply_impulse(&self, rigid_bodies: &mut IndexMap<Entity, RigidBody>, impulse: Vec3) {
+        rigid_bodies[self.a].apply_velocity_impulse(-impulse, self.r_a);
+        rigid_bodies[self.b].apply_velocity_impulse(impulse, self.r_b);
+    }
+
+    fn solve(&mut self, rigid_bodies: &mut IndexMap<Entity, RigidBody>) {
+        // Normal impulse, accumulated and clamped so contacts can only push
+        let normal_vel = self.relative_velocity(rigid_bodies).dot(self.normal);
+        let lambda = (self.target_vel - normal_vel) * self.normal_mass;
+        let new_impulse = (self.normal_impulse + lambda).max(0.0);
+        let delta = new_impulse - self.normal_impulse;
+        self.normal_impulse = new_impulse;
+        self.apply_impulse(rigid_bodies, delta * self.normal);
+
+        // Friction impulse. Sticks while inside the static cone, slides with
+        // dynamic friction once it leaves it.
+        let tangent_vel = self
+            .relative_velocity(rigid_bodies)
+            .reject_from_normalized(self.normal);
+        let tangent_dir = tangent_vel.normalize_or_zero();
+        if tangent_dir == Vec3::ZERO {
+            return;
+        }
+        let tangent_mass = effective_mass(
+            &rigid_bodies[self.a],
+            &rigid_bodies[self.b],
+            self.r_a,
+            self.r_b,
+            tangent_dir,
+        );
+        let mut new_impulse = self.tangent_impulse - tangent_vel * tangent_mass;
+        let static_limit = self.material.static_friction * self.normal_impulse;
+        if new_impulse.length() > static_limit {
+            new_impulse = new_impulse.normalize_or_zero()
+                * self.material.dynamic_friction
+                * self.normal_impulse;
+        }
+        let delta = new_impulse - self.tangent_impulse;
+        self.tangent_impulse = new_impulse;
+        self.apply_impulse(rigid_bodies, delta);
+    }
+}
+
+// Solves contacts and joints together so neither undoes the other
+pub(crate) struct ContactSolver {
+    constraints: Vec<ContactConstraint>,
+    joints: Vec<JointConstraint>,
+}
+
+impl ContactSolver {
+    pub fn new(
+        rigid_bodies: &IndexMap<Entity, RigidBody>,
+        touches: &[(usize, usize, ContactManifold)],
+        joints: &[Joint],
+        pair_caches: &IndexMap<(Entity, Entity), PairCache>,
+        dt: f32,
+    ) -> Self {
+        let mut constraints = vec![];
+        for (a, b, manifold) in touches {
+            if rigid_bodies[*a].inv_mass() + rigid_bodies[*b].inv_mass() == 0.0 {
+                continue;
+            }
+            let cache = pair_caches.get(&pair_key(rigid_bodies, *a, *b));
+            for contact in &manifold.points {
+                constraints.push(ContactConstraint::new(
+                    rigid_bodies,
+                    *a,
+                    *b,
+                    manifold.normal,
+                    contact.point,
+                    cache,
+                ));
+            }
+        }
+        let joints = joints
+            .iter()
+            .filter_map(|joint| JointConstraint::new(rigid_bodies, joint, dt))
+            .collect();
+        Self {
+            constraints,
+            joints,
+        }
+    }
+
+    pub fn solve(&mut self, rigid_bodies: &mut IndexMap<Entity, RigidBody>) {
+        for constraint in &self.constraints {
+            constraint.warm_start(rigid_bodies);
+        }
+        for _ in 0..SOLVER_ITERATIONS {
+            for joint in self.joints.iter_mut() {
+                joint.solve(rigid_bodies);
+            }
+            for constraint in self.constraints.iter_mut() {
+                constraint.solve(rigid_bodies);
+            }
+        }
+    }
+
+    // Keeps the impulses each contact ended with for the next step. Pairs
+    // that were tested but aren't touching anymore lose their old contacts.
+    pub fn store_impulses(
+        &self,
+        rigid_bodies: &IndexMap<Entity, RigidBody>,
+        tested: &[(usize, usize)],
+        pair_caches: &mut IndexMap<(Entity, Entity), PairCache>,
+    ) {
+        for &(a, b) in tested {
+            if let Some(cache) = pair_caches.get_mut(&pair_key(rigid_bodies, a, b)) {
+                cache.contacts.clear();
+            }
+        }
+        for constraint in &self.constraints {
+            let key = pair_key(rigid_bodies, constraint.a, constraint.b);
+            if let Some(cache) = pair_caches.get_mut(&key) {
+                cache.contacts.push(CachedContact {
+                    local_point: constraint.local_point,
+                    normal_impulse: constraint.normal_impulse,
+                    tangent_impulse: constraint.tangent_impulse,
+                });
+            }
+        }
+    }
+}
+
+pub(crate) fn pair_key(
+    rigid_bodies: &IndexMap<Entity, RigidBody>,
+    a: usize,
+    b: usize,
+) -> (Entity, Entity) {
+    (
+        *rigid_bodies.get_index(a).unwrap().0,
+        *rigid_bodies.get_index(b).unwrap().0,
+    )
+}
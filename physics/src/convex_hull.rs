@@ -0,0 +1,242 @@
+use glam::Vec3;
+use hashbrown::HashSet;
+
+// Faces whose normals are this close get merged into one polygon
+const COPLANAR_THRESHOLD: f32 = 0.99999;
+
+#[derive(Debug, Clone)]
+struct HullFace {
+    idxs: [usize; 3],
+    normal: Vec3,
+    offset: f32,
+    outside: Vec<usize>,
+}
+
+impl HullFace {
+    fn new(points: &[Vec3], idxs: [usize; 3]) -> Self {
+        let [a, b, c] = idxs.map(|i| points[i]);
+        let normal = (b - a).cross(c - a).normalize_or_zero();
+        Self {
+            idxs,
+            normal,
+            offset: normal.dot(a),
+            outside: vec![],
+        }
+    }
+
+    fn dist(&self, p: Vec3) -> f32 {
+        self.normal.dot(p) - self.offset
+    }
+
+    fn edges(&self) -> [[usize; 2]; 3] {
+        let [a, b, c] = self.idxs;
+        [[a, b], [b, c], [c, a]]
+    }
+}
+
+// Convex hull of a point cloud as polygons wound counter clockwise around
+// their outward normals
+pub(crate) struct ConvexHull {
+    pub points: Vec<Vec3>,
+    pub edges: Vec<[u32; 2]>,
+    pub faces: Vec<Vec<u32>>,
+}
+
+impl ConvexHull {
+    // Quickhull. With max_vertices set, stops adding points once the hull has
+    // that many vertices, always adding the point farthest out of any face
+    // first.
+    pub fn new(points: &[Vec3], max_vertices: Option<usize>) -> anyhow::Result<Self> {
+        if max_vertices.is_some_and(|max| max < 4) {
+            anyhow::bail!("convex hull needs at least 4 vertices");
+        }
+        let scale = points
+            .iter()
+            .fold(Vec3::ZERO, |acc, p| acc.max(p.abs()))
+            .element_sum();
+        let eps = scale * 0.00001;
+
+        let mut faces = initial_tetrahedron(points, eps)?;
+        assign_outside(points, &mut faces, (0..points.len()).collect(), eps);
+
+        while max_vertices.is_none_or(|max| vertex_count(&faces) < max) {
+            let Some((eye, _)) = faces
+                .iter()
+                .flat_map(|f| f.outside.iter().map(move |&i| (i, f.dist(points[i]))))
+                .max_by(|x, y| x.1.total_cmp(&y.1))
+            else {
+                break;
+            };
+            // Remove every face the eye can see, keeping the edges on the
+            // boundary of that region
+            let mut horizon: Vec<[usize; 2]> = vec![];
+            let mut orphans = vec![];
+            faces.retain_mut(|face| {
+                if face.dist(points[eye]) <= eps {
+                    return true;
+                }
+                for edge in face.edges() {
+                    if let Some(pos) = horizon.iter().position(|e| *e == [edge[1], edge[0]]) {
+                        horizon.swap_remove(pos);
+                    } else {
+                        horizon.push(edge);
+                    }
+                }
+                orphans.append(&mut face.outside);
+                false
+            });
+            let first_new = faces.len();
+            for edge in horizon {
+                faces.push(HullFace::new(points, [edge[0], edge[1], eye]));
+            }
+            orphans.retain(|&i| i != eye);
+            assign_outside(points, &mut faces[first_new..], orphans, eps);
+        }
+
+        Ok(Self::from_triangles(points, &faces))
+    }
+
+    // Merges coplanar neighbours into polygons and compacts the vertex list
+    fn from_triangles(points: &[Vec3], faces: &[HullFace]) -> Self {
+        let mut group: Vec<usize> = (0..faces.len()).collect();
+        for (i, face) in faces.iter().enumerate() {
+            for (j, other) in faces.iter().enumerate().skip(i + 1) {
+                let shares_edge = face
+                    .edges()
+                    .iter()
+                    .any(|e| other.edges().contains(&[e[1], e[0]]));
+                if shares_edge && face.normal.dot(other.normal) >= COPLANAR_THRESHOLD {
+                    let (ri, rj) = (root(&mut group, i), root(&mut group, j));
+                    group[rj] = ri;
+                }
+            }
+        }
+
+        let mut remap = vec![None; points.len()];
+        let mut out = Self {
+            points: vec![],
+            edges: vec![],
+            faces: vec![],
+        };
+        for r in 0..faces.len() {
+            if root(&mut group, r) != r {
+                continue;
+            }
+            // Boundary edges of the merged triangles, chained into a loop
+            let mut boundary: Vec<[usize; 2]> = vec![];
+            for (i, face) in faces.iter().enumerate() {
+                if root(&mut group, i) != r {
+                    continue;
+                }
+                for edge in face.edges() {
+                    if let Some(pos) = boundary.iter().position(|e| *e == [edge[1], edge[0]]) {
+                        boundary.swap_remove(pos);
+                    } else {
+                        boundary.push(edge);
+                    }
+                }
+            }
+            let mut polygon = vec![boundary[0][0]];
+            let mut next = boundary[0][1];
+            while next != polygon[0] && polygon.len() < boundary.len() {
+                polygon.push(next);
+                next = match boundary.iter().find(|e| e[0] == next) {
+                    Some(edge) => edge[1],
+                    None => break,
+                };
+            }
+            let polygon: Vec<u32> = polygon
+                .into_iter()
+                .map(|i| {
+                    *remap[i].get_or_insert_with(|| {
+                        out.points.push(points[i]);
+                        out.points.len() as u32 - 1
+                    })
+                })
+                .collect();
+            for (k, &i) in polygon.iter().enumerate() {
+                let j = polygon[(k + 1) % polygon.len()];
+                if !out.edges.contains(&[j, i]) {
+                    out.edges.push([i, j]);
+                }
+            }
+            out.faces.push(polygon);
+        }
+        out
+    }
+}
+
+fn initial_tetrahedron(points: &[Vec3], eps: f32) -> anyhow::Result<Vec<HullFace>> {
+    let farthest_from = |dist: &dyn Fn(Vec3) -> f32| {
+        let mut best = 0;
+        for (i, &p) in points.iter().enumerate() {
+            if dist(p) > dist(points[best]) {
+                best = i;
+            }
+        }
+        best
+    };
+    if points.len() < 4 {
+        anyhow::bail!("convex hull needs at least 4 points, got {}", points.len());
+    }
+    // Widest pair of extreme points along the axes
+    let mut a = 0;
+    let mut b = 0;
+    for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+        let min = farthest_from(&|p| -p.dot(axis));
+        let max = farthest_from(&|p| p.dot(axis));
+        if points[max].distance(points[min]) > points[b].distance(points[a]) {
+            (a, b) = (min, max);
+        }
+    }
+    if points[b].distance(points[a]) <= eps {
+        anyhow::bail!("convex hull points are all the same");
+    }
+    let ab = (points[b] - points[a]).normalize();
+    let c = farthest_from(&|p| (p - points[a]).reject_from_normalized(ab).length());
+    if (points[c] - points[a]).reject_from_normalized(ab).length() <= eps {
+        anyhow::bail!("convex hull points are collinear");
+    }
+    let n = ab.cross(points[c] - points[a]).normalize();
+    let d = farthest_from(&|p| (p - points[a]).dot(n).abs());
+    let d_dist = (points[d] - points[a]).dot(n);
+    if d_dist.abs() <= eps {
+        anyhow::bail!("convex hull points are coplanar");
+    }
+    // Wind the base away from the apex
+    let (b, c) = if d_dist > 0.0 { (c, b) } else { (b, c) };
+    Ok(vec![
+        HullFace::new(points, [a, b, c]),
+        HullFace::new(points, [a, d, b]),
+        HullFace::new(points, [b, d, c]),
+        HullFace::new(points, [c, d, a]),
+    ])
+}
+
+fn root(group: &mut [usize], mut i: usize) -> usize {
+    while group[i] != i {
+        group[i] = group[group[i]];
+        i = group[i];
+    }
+    i
+}
+
+// Points used by the faces, earlier ones can end up inside the hull
+fn vertex_count(faces: &[HullFace]) -> usize {
+    faces
+        .iter()
+        .flat_map(|f| f.idxs)
+        .collect::<HashSet<_>>()
+        .len()
+}
+
+fn assign_outside(points: &[Vec3], faces: &mut [HullFace], candidates: Vec<usize>, eps: f32) {
+    for i in candidates {
+        for face in faces.iter_mut() {
+            if face.dist(points[i]) > eps {
+                face.outside.push(i);
+                break;
+            }
+        }
+    }
+}
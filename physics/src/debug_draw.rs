@@ -0,0 +1,206 @@
+use std::f32::consts::TAU;
+
+use glam::Vec3;
+
+use crate::{
+    BodyType, PhysicsManager, RigidBody, aabb::Aabb, collision_shape::CollisionShape,
+    joint::JointKind,
+};
+
+const CIRCLE_SEGMENTS: usize = 16;
+const POINT_SIZE: f32 = 0.05;
+const NORMAL_LENGTH: f32 = 0.3;
+const FRAME_SIZE: f32 = 0.2;
+
+const STATIC_COLOR: Vec3 = Vec3::new(0.5, 0.5, 0.5);
+const KINEMATIC_COLOR: Vec3 = Vec3::new(0.8, 0.5, 1.0);
+const AWAKE_COLOR: Vec3 = Vec3::new(0.2, 1.0, 0.2);
+const SLEEPING_COLOR: Vec3 = Vec3::new(0.2, 0.4, 0.8);
+const SENSOR_COLOR: Vec3 = Vec3::new(1.0, 1.0, 0.2);
+const AABB_COLOR: Vec3 = Vec3::new(1.0, 0.5, 0.0);
+const CONTACT_COLOR: Vec3 = Vec3::new(1.0, 0.1, 0.1);
+const NORMAL_COLOR: Vec3 = Vec3::new(1.0, 0.2, 1.0);
+const JOINT_COLOR: Vec3 = Vec3::new(1.0, 1.0, 1.0);
+const JOINT_AXIS_COLOR: Vec3 = Vec3::new(0.2, 1.0, 1.0);
+
+// Whatever the debug output gets drawn with
+pub trait DebugDraw {
+    fn line(&mut self, from: Vec3, to: Vec3, color: Vec3);
+    fn point(&mut self, at: Vec3, color: Vec3);
+}
+
+// Collects the output as a line list, points become small crosses
+#[derive(Debug, Clone, Default)]
+pub struct DebugLines {
+    // From, to and color of each line
+    pub lines: Vec<[Vec3; 3]>,
+}
+
+impl DebugDraw for DebugLines {
+    fn line(&mut self, from: Vec3, to: Vec3, color: Vec3) {
+        self.lines.push([from, to, color]);
+    }
+
+    fn point(&mut self, at: Vec3, color: Vec3) {
+        for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+            let offset = axis * POINT_SIZE;
+            self.line(at - offset, at + offset, color);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DebugDrawOptions {
+    pub shapes: bool,
+    pub aabbs: bool,
+    pub contacts: bool,
+    pub joints: bool,
+}
+
+impl Default for DebugDrawOptions {
+    fn default() -> Self {
+        Self {
+            shapes: true,
+            aabbs: false,
+            contacts: true,
+            joints: true,
+        }
+    }
+}
+
+fn draw_circle(out: &mut impl DebugDraw, center: Vec3, normal: Vec3, radius: f32, color: Vec3) {
+    let u = normal.any_orthonormal_vector() * radius;
+    let v = normal.cross(u);
+    let at = |i: usize| {
+        let theta = i as f32 / CIRCLE_SEGMENTS as f32 * TAU;
+        center + u * theta.cos() + v * theta.sin()
+    };
+    for i in 0..CIRCLE_SEGMENTS {
+        out.line(at(i), at(i + 1), color);
+    }
+}
+
+fn draw_aabb(out: &mut impl DebugDraw, aabb: &Aabb, color: Vec3) {
+    let corner = |i: usize| {
+        Vec3::select(
+            glam::BVec3::new(i & 1 != 0, i & 2 != 0, i & 4 != 0),
+            aabb.max,
+            aabb.min,
+        )
+    };
+    for i in 0..8 {
+        for bit in [1, 2, 4] {
+            if i & bit == 0 {
+                out.line(corner(i), corner(i | bit), color);
+            }
+        }
+    }
+}
+
+// Wireframe of a shape already in world space
+fn draw_shape(out: &mut impl DebugDraw, shape: &CollisionShape, color: Vec3) {
+    match shape {
+        CollisionShape::Sphere(sphere) => {
+            for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+                draw_circle(out, sphere.center, axis, sphere.radius, color);
+            }
+        }
+        CollisionShape::Capsule(capsule) => {
+            let axis = (capsule.b - capsule.a).normalize_or(Vec3::Y);
+            let u = axis.any_orthonormal_vector();
+            let v = axis.cross(u);
+            for end in [capsule.a, capsule.b] {
+                for normal in [axis, u, v] {
+                    draw_circle(out, end, normal, capsule.radius, color);
+                }
+            }
+            for side in [u, -u, v, -v] {
+                let offset = side * capsule.radius;
+                out.line(capsule.a + offset, capsule.b + offset, color);
+            }
+        }
+        CollisionShape::Mesh(mesh) => {
+            for [i, j] in &mesh.edges {
+                out.line(mesh.points[*i as usize], mesh.points[*j as usize], color);
+            }
+        }
+        CollisionShape::Compound(_) | CollisionShape::TriangleMesh(_) => {
+            for part in shape.convex_parts() {
+                draw_shape(out, &part, color);
+            }
+        }
+    }
+}
+
+fn body_color(rb: &RigidBody) -> Vec3 {
+    if rb.is_sensor {
+        SENSOR_COLOR
+    } else if rb.body_type == BodyType::Static {
+        STATIC_COLOR
+    } else if rb.body_type == BodyType::Kinematic {
+        KINEMATIC_COLOR
+    } else if rb.is_sleeping() {
+        SLEEPING_COLOR
+    } else {
+        AWAKE_COLOR
+    }
+}
+
+impl PhysicsManager {
+    // Draws the current state of the world. Contacts are the ones the last
+    // step solved, with the normal EPA gave for each.
+    pub fn debug_draw(&self, options: &DebugDrawOptions, out: &mut impl DebugDraw) {
+        for rb in self.rigid_bodies.values() {
+            if options.shapes {
+                draw_shape(out, &rb.orient_shape, body_color(rb));
+            }
+            if options.aabbs {
+                draw_aabb(out, &rb.aabb, AABB_COLOR);
+            }
+        }
+        if options.contacts {
+            for (_, _, manifold) in &self.contacts {
+                for contact in &manifold.points {
+                    out.point(contact.point, CONTACT_COLOR);
+                    out.line(
+                        contact.point,
+                        contact.point + manifold.normal * NORMAL_LENGTH,
+                        NORMAL_COLOR,
+                    );
+                }
+            }
+        }
+        if options.joints {
+            for joint in &self.joints {
+                let (Some(rb_a), Some(rb_b)) = (
+                    self.rigid_bodies.get(&joint.body_a),
+                    self.rigid_bodies.get(&joint.body_b),
+                ) else {
+                    continue;
+                };
+                let anchor_a = rb_a
+                    .orient
+                    .to_transform()
+                    .transform_point3(joint.local_anchor_a);
+                let anchor_b = rb_b
+                    .orient
+                    .to_transform()
+                    .transform_point3(joint.local_anchor_b);
+                out.point(anchor_a, JOINT_COLOR);
+                out.point(anchor_b, JOINT_COLOR);
+                out.line(anchor_a, anchor_b, JOINT_COLOR);
+                // Frame of body a at its anchor
+                let rot_a = rb_a.rotation();
+                for (axis, color) in [(Vec3::X, Vec3::X), (Vec3::Y, Vec3::Y), (Vec3::Z, Vec3::Z)] {
+                    out.line(anchor_a, anchor_a + rot_a * axis * FRAME_SIZE, color);
+                }
+                if let JointKind::Hinge { axis, .. } | JointKind::Prismatic { axis, .. } =
+                    &joint.kind
+                {
+                    let axis = rot_a * *axis * FRAME_SIZE * 2.0;
+                    out.line(anchor_a - axis, anchor_a + axis, JOINT_AXIS_COLOR);
+                }
+            }
+        }
+    }
+}
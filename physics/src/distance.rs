@@ -0,0 +1,148 @@
+use std::borrow::Cow;
+
+use glam::Vec3;
+
+use crate::{
+    PhysicsManager,
+    collision_shape::{Capsule, CollisionShape, Sphere},
+    handle::BodyHandle,
+    simplex::{SupportPoint, closest_point_to},
+};
+
+const DISTANCE_MAX_ITERATIONS: usize = 64;
+// Stop once a new support point gets the distance this much closer, relative
+// to the distance itself
+const DISTANCE_REL_EPSILON: f32 = 0.00001;
+// Shapes closer than this count as touching
+const DISTANCE_EPSILON: f32 = 0.0001;
+
+#[derive(Debug, Clone)]
+pub struct DistanceInfo {
+    pub distance: f32,
+    // Closest points on the surface of each shape
+    pub point_a: Vec3,
+    pub point_b: Vec3,
+    // Separating direction, from a towards b
+    pub normal: Vec3,
+}
+
+// Spheres and capsules split into a point or segment and the radius around
+// it. GJK only converges slowly on round surfaces, but exactly on their cores.
+pub(crate) fn core_and_radius(shape: &CollisionShape) -> (Cow<'_, CollisionShape>, f32) {
+    match shape {
+        CollisionShape::Sphere(sphere) => (
+            Cow::Owned(CollisionShape::Sphere(Sphere {
+                center: sphere.center,
+                radius: 0.0,
+            })),
+            sphere.radius,
+        ),
+        CollisionShape::Capsule(capsule) => (
+            Cow::Owned(CollisionShape::Capsule(Capsule {
+                a: capsule.a,
+                b: capsule.b,
+                radius: 0.0,
+            })),
+            capsule.radius,
+        ),
+        _ => (Cow::Borrowed(shape), 0.0),
+    }
+}
+
+impl DistanceInfo {
+    // Closest points between two convex shapes. None if they touch.
+    pub fn new_with_gjk(a: &CollisionShape, b: &CollisionShape) -> Option<Self> {
+        Self::new_with_margin(a, b, 0.0)
+    }
+
+    // Same as new_with_gjk with both shapes grown by margin. Only the shapes
+    // themselves have to be apart, when just the margins overlap the
+    // distance comes out negative.
+    pub fn new_with_margin(a: &CollisionShape, b: &CollisionShape, margin: f32) -> Option<Self> {
+        let (core_a, radius_a) = core_and_radius(a);
+        let (core_b, radius_b) = core_and_radius(b);
+        let support = |dir: Vec3| {
+            let on_a = core_a.farthest_point_along(dir);
+            let on_b = core_b.farthest_point_along(-dir);
+            SupportPoint {
+                p: on_a - on_b,
+                on_a,
+                on_b,
+            }
+        };
+        // Closest point of the minkowski difference to the origin, found by
+        // walking the simplex towards it
+        let first = support(core_b.center_hint() - core_a.center_hint());
+        let mut simplex = vec![first];
+        let (mut v, mut on_a, mut on_b) = (first.p, first.on_a, first.on_b);
+        for _ in 0..DISTANCE_MAX_ITERATIONS {
+            if v.length_squared() <= DISTANCE_EPSILON * DISTANCE_EPSILON {
+                return None;
+            }
+            let w = support(-v);
+            if v.length_squared() - v.dot(w.p) <= DISTANCE_REL_EPSILON * v.length_squared()
+                || simplex
+                    .iter()
+                    .any(|s| s.p.distance_squared(w.p) <= f32::EPSILON)
+            {
+                break;
+            }
+            simplex.push(w);
+            let closest = closest_point_to(&mut simplex, Vec3::ZERO);
+            if simplex.len() == 4 {
+                // Origin enclosed, the shapes overlap
+                return None;
+            }
+            (v, on_a, on_b) = (closest.point, closest.on_a, closest.on_b);
+        }
+        let core_dist = v.length();
+        if core_dist - radius_a - radius_b <= DISTANCE_EPSILON {
+            return None;
+        }
+        let normal = -v / core_dist;
+        let (grow_a, grow_b) = (radius_a + margin, radius_b + margin);
+        Some(Self {
+            distance: core_dist - grow_a - grow_b,
+            point_a: on_a + normal * grow_a,
+            point_b: on_b - normal * grow_b,
+            normal,
+        })
+    }
+}
+
+impl PhysicsManager {
+    // Closest points between two bodies over all of their convex parts. None
+    // if either body is gone or they touch.
+    pub fn body_distance(&self, a: BodyHandle, b: BodyHandle, margin: f32) -> Option<DistanceInfo> {
+        let (rb_a, rb_b) = (self.body(a)?, self.body(b)?);
+        let parts_a = rb_a.orient_shape.convex_parts();
+        let parts_b = rb_b.orient_shape.convex_parts();
+        let mut pairs = vec![];
+        for part_a in &parts_a {
+            let aabb_a = part_a.aabb();
+            for part_b in &parts_b {
+                pairs.push((aabb_a.distance(&part_b.aabb()), part_a, part_b));
+            }
+        }
+        // Nearest bounds first, the rest can be skipped once they are further
+        // away than the best pair found
+        pairs.sort_by(|x, y| x.0.total_cmp(&y.0));
+        let mut best: Option<DistanceInfo> = None;
+        for (bounds_dist, part_a, part_b) in pairs {
+            if best
+                .as_ref()
+                .is_some_and(|best| bounds_dist - 2.0 * margin >= best.distance)
+            {
+                break;
+            }
+            let info = DistanceInfo::new_with_margin(part_a, part_b, margin)?;
+            if best
+                .as_ref()
+                .is_none_or(|best| info.distance < best.distance)
+            {
+                best = Some(info);
+            }
+        }
+        best
+    }
+}
@@ -0,0 +1,9 @@
+use glam::Vec3;
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum CollisionError {
+    #[error("degenerate triangle {0}, {1}, {2}")]
+    DegenerateTriangle(Vec3, Vec3, Vec3),
+    #[error("EPA polytope has no faces left")]
+    EmptyPolytope,
+}
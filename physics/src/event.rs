@@ -0,0 +1,91 @@
+use common::Entity;
+use indexmap::{IndexMap, IndexSet};
+use serde::{Deserialize, Serialize};
+
+use crate::{PhysicsManager, RigidBody, contact_manifold::ContactManifold};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PhysicsEvent {
+    SensorBegin { sensor: Entity, other: Entity },
+    SensorPersist { sensor: Entity, other: Entity },
+    SensorEnd { sensor: Entity, other: Entity },
+    CollisionBegin { a: Entity, b: Entity },
+    CollisionEnd { a: Entity, b: Entity },
+}
+
+// Pairs that weren't tested this step because neither body moves keep
+// whatever state they had
+fn carry_over_resting(
+    rigid_bodies: &IndexMap<Entity, RigidBody>,
+    previous: &IndexSet<(Entity, Entity)>,
+    current: &mut IndexSet<(Entity, Entity)>,
+) {
+    for (a, b) in previous {
+        let (Some(rb_a), Some(rb_b)) = (rigid_bodies.get(a), rigid_bodies.get(b)) else {
+            continue;
+        };
+        if !rb_a.is_active() && !rb_b.is_active() {
+            current.insert((*a, *b));
+        }
+    }
+}
+
+impl PhysicsManager {
+    pub(crate) fn update_events(
+        &mut self,
+        touches: &[(usize, usize, ContactManifold)],
+        overlaps: &[(usize, usize)],
+    ) {
+        let rigid_bodies = &self.rigid_bodies;
+        let entity = |i: usize| *rigid_bodies.get_index(i).unwrap().0;
+
+        let mut touching: IndexSet<_> = touches
+            .iter()
+            .map(|(i, j, _)| (entity(*i), entity(*j)))
+            .collect();
+        carry_over_resting(rigid_bodies, &self.touching, &mut touching);
+        for &(a, b) in touching.difference(&self.touching) {
+            self.events.push(PhysicsEvent::CollisionBegin { a, b });
+        }
+        for &(a, b) in self.touching.difference(&touching) {
+            self.events.push(PhysicsEvent::CollisionEnd { a, b });
+        }
+        self.touching = touching;
+
+        let mut sensed = IndexSet::new();
+        for &(i, j) in overlaps {
+            let (rb_a, rb_b) = (&rigid_bodies[i], &rigid_bodies[j]);
+            if !(rb_a.is_sensor || rb_b.is_sensor)
+                || !(rb_a.is_active() || rb_b.is_active())
+                || !rb_a.filter.can_interact(&rb_b.filter)
+                || !rb_a.overlaps(rb_b)
+            {
+                continue;
+            }
+            if rb_a.is_sensor {
+                sensed.insert((entity(i), entity(j)));
+            } else {
+                sensed.insert((entity(j), entity(i)));
+            }
+        }
+        carry_over_resting(rigid_bodies, &self.sensed, &mut sensed);
+        for &(sensor, other) in &sensed {
+            if self.sensed.contains(&(sensor, other)) {
+                self.events
+                    .push(PhysicsEvent::SensorPersist { sensor, other });
+            } else {
+                self.events
+                    .push(PhysicsEvent::SensorBegin { sensor, other });
+            }
+        }
+        for &(sensor, other) in self.sensed.difference(&sensed) {
+            self.events.push(PhysicsEvent::SensorEnd { sensor, other });
+        }
+        self.sensed = sensed;
+    }
+
+    // Events collected since the last drain, in the order they happened
+    pub fn drain_events(&mut self) -> Vec<PhysicsEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
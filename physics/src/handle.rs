@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+
+// Stable reference to a body in a PhysicsManager. Stays valid while other
+// bodies come and go, and stops resolving once its own body is removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BodyHandle {
+    slot: u32,
+    generation: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Slot {
+    generation: u32,
+    // Index of the body in the manager's body map
+    body: Option<usize>,
+}
+
+// Maps handles to body indices. Bodies are stored densely and removed by
+// swapping in the last one, so the slot of the moved body gets repointed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct BodySlots {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    slot_of_body: Vec<u32>,
+}
+
+impl BodySlots {
+    // Handle for a body just pushed onto the end of the body map
+    pub fn insert(&mut self) -> BodyHandle {
+        let body = self.slot_of_body.len();
+        let slot = match self.free.pop() {
+            Some(slot) => {
+                self.slots[slot as usize].body = Some(body);
+                slot
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    body: Some(body),
+                });
+                self.slots.len() as u32 - 1
+            }
+        };
+        self.slot_of_body.push(slot);
+        BodyHandle {
+            slot,
+            generation: self.slots[slot as usize].generation,
+        }
+    }
+
+    pub fn get(&self, handle: BodyHandle) -> Option<usize> {
+        let slot = self.slots.get(handle.slot as usize)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        slot.body
+    }
+
+    pub fn handle_of(&self, body: usize) -> Option<BodyHandle> {
+        let slot = *self.slot_of_body.get(body)?;
+        Some(BodyHandle {
+            slot,
+            generation: self.slots[slot as usize].generation,
+        })
+    }
+
+    // Frees the handle's slot and returns the index to swap remove
+    pub fn remove(&mut self, handle: BodyHandle) -> Option<usize> {
+        let body = self.get(handle)?;
+        let slot = &mut self.slots[handle.slot as usize];
+        slot.body = None;
+        slot.generation += 1;
+        self.free.push(handle.slot);
+        self.slot_of_body.swap_remove(body);
+        if let Some(&moved) = self.slot_of_body.get(body) {
+            self.slots[moved as usize].body = Some(body);
+        }
+        Some(body)
+    }
+}
@@ -1,9 +1,79 @@
 use glam::{Vec3, Vec4Swizzles};
 
-use crate::{collision_shape::CollisionShape, utils::get_triangle_plane};
+use crate::{
+    PENETRATION_SLOP,
+    collision_shape::CollisionShape,
+    distance::{DistanceInfo, core_and_radius},
+    error::CollisionError,
+    simplex::{SupportPoint, closest_point_to},
+    utils::get_triangle_plane,
+};
 
+const GJK_MAX_ITERATIONS: usize = 64;
+const GJK_EPSILON: f32 = 0.0001;
+const EPA_MAX_ITERATIONS: usize = 64;
 const EPA_PROGRESS_EPSILON: f32 = 0.0001;
-const MIN_EPA_CYCLES: usize = 4;
+
+// Polytope face with its outward normal and distance from the origin
+#[derive(Debug, Clone)]
+struct EpaFace {
+    idxs: [usize; 3],
+    normal: Vec3,
+    dist: f32,
+}
+
+impl EpaFace {
+    fn new(points: &[Vec3], idxs: [usize; 3]) -> Result<Self, CollisionError> {
+        let plane = get_triangle_plane(points[idxs[0]], points[idxs[1]], points[idxs[2]])?;
+        Ok(Self {
+            idxs,
+            normal: plane.xyz(),
+            dist: -plane.w,
+        })
+    }
+}
+
+// How close to the origin still counts as on it. Grows with the size of the
+// simplex as big shapes lose more precision, but stays well under the slop so
+// bodies resting on big floors keep their contacts.
+fn surface_tolerance(simplex: &[SupportPoint]) -> f32 {
+    let scale = simplex
+        .iter()
+        .map(|s| s.p.length_squared())
+        .fold(1.0, f32::max)
+        .sqrt();
+    (GJK_EPSILON * scale).min(PENETRATION_SLOP * 0.1)
+}
+
+// Support point furthest off the point, line or plane of a simplex with the
+// origin on it. None if the minkowski difference is flat there too.
+fn grow_sideways(
+    simplex: &[SupportPoint],
+    support: impl Fn(Vec3) -> SupportPoint,
+) -> Option<SupportPoint> {
+    let dirs = match simplex.len() {
+        1 => vec![Vec3::X, Vec3::Y, Vec3::Z],
+        2 => {
+            let axis = (simplex[1].p - simplex[0].p).normalize_or(Vec3::X);
+            let perp = axis.any_orthonormal_vector();
+            vec![perp, axis.cross(perp)]
+        }
+        _ => vec![
+            (simplex[1].p - simplex[0].p)
+                .cross(simplex[2].p - simplex[0].p)
+                .normalize_or(Vec3::X),
+        ],
+    };
+    let (offset, point) = dirs
+        .into_iter()
+        .flat_map(|dir| [dir, -dir])
+        .map(|dir| {
+            let point = support(dir);
+            (dir.dot(point.p - simplex[0].p), point)
+        })
+        .max_by(|x, y| x.0.total_cmp(&y.0))?;
+    (offset > surface_tolerance(simplex)).then_some(point)
+}
 
 #[derive(Debug, Clone)]
 pub struct IntersectionInfo {
@@ -12,112 +82,177 @@ pub struct IntersectionInfo {
 }
 
 impl IntersectionInfo {
-    pub fn new_with_gjk(a: &CollisionShape, b: &CollisionShape) -> Option<IntersectionInfo> {
-        let mut supp_points: Vec<Vec3> = vec![];
-        loop {
-            let check_dir = if supp_points.len() == 0 {
-                (b.center_hint() - a.center_hint()).normalize()
-            } else if supp_points.len() == 1 {
-                -supp_points[0].normalize()
-            } else if supp_points.len() == 2 {
-                let ab = supp_points[1] - supp_points[0];
-                let ao = -supp_points[0];
-                let po = ao.reject_from(ab);
-                if po.length_squared() == 0.0 {
-                    // Origin is on AB
-                    let sample = if ab.x >= 0.5 { Vec3::Y } else { Vec3::X };
-                    let p_dir = sample.reject_from(ab);
-                    p_dir.normalize()
-                } else {
-                    po.normalize()
-                }
-            } else if supp_points.len() == 3 {
-                let abc = get_triangle_plane(supp_points[0], supp_points[1], supp_points[2]);
-                if abc.w >= 0.0 { abc.xyz() } else { -abc.xyz() }
-            } else {
-                break;
-            };
-            let farth_point_a = a.farthest_point_along(check_dir);
-            let farth_point_b = b.farthest_point_along(-check_dir);
-            let supp_point = farth_point_a - farth_point_b;
+    pub fn new_with_gjk(
+        a: &CollisionShape,
+        b: &CollisionShape,
+    ) -> Result<Option<IntersectionInfo>, CollisionError> {
+        let mut dir = (b.center_hint() - a.center_hint()).normalize_or(Vec3::X);
+        Self::new_with_gjk_from(a, b, &mut dir)
+    }
+
+    // Starts the search along dir and leaves the direction it finished along
+    // in it. Shapes that barely moved since finish in a step or two when given
+    // the same dir again. Ok(None) if the shapes are apart or only touch.
+    pub fn new_with_gjk_from(
+        a: &CollisionShape,
+        b: &CollisionShape,
+        dir: &mut Vec3,
+    ) -> Result<Option<IntersectionInfo>, CollisionError> {
+        if let Some(rounded) = Self::new_with_cores(a, b) {
+            if let Some(info) = &rounded {
+                *dir = info.dir;
+            }
+            return Ok(rounded);
+        }
+        // Grow the simplex towards the origin, keeping only the part of it
+        // closest to the origin, until it encloses the origin or a separating
+        // direction shows up
+        let support = |dir: Vec3| {
+            let on_a = a.farthest_point_along(dir);
+            let on_b = b.farthest_point_along(-dir);
+            SupportPoint {
+                p: on_a - on_b,
+                on_a,
+                on_b,
+            }
+        };
+        let mut simplex = vec![];
+        let mut check_dir = *dir;
+        let mut on_origin = false;
+        for _ in 0..GJK_MAX_ITERATIONS {
+            let supp_point = support(check_dir);
             // Check if new point crossed origin
-            let supp_pt_check_dist = check_dir.dot(supp_point);
-            if supp_pt_check_dist < 0.0 {
-                return None;
+            if check_dir.dot(supp_point.p) < 0.0 {
+                *dir = check_dir;
+                return Ok(None);
+            }
+            if simplex
+                .iter()
+                .any(|s: &SupportPoint| s.p.distance_squared(supp_point.p) <= f32::EPSILON)
+            {
+                // Simplex can't grow any further
+                break;
             }
-            supp_points.push(supp_point);
-            if supp_point == Vec3::ZERO {
-                // println!("touch found");
-                return Some(Self {
-                    dir: check_dir,
-                    dist: 0.0,
-                });
+            simplex.push(supp_point);
+            let grown = simplex.clone();
+            let closest = closest_point_to(&mut simplex, Vec3::ZERO);
+            if simplex.len() == 4 {
+                break;
+            }
+            if closest.point.length() > surface_tolerance(&simplex) {
+                check_dir = -closest.point.normalize();
+                continue;
             }
+            // Origin is on the simplex itself, keep all of it
+            simplex = grown;
+            on_origin = true;
+            break;
+        }
+        if simplex.len() < 4 && !on_origin {
+            // Stopped short of the origin, so it is outside or right on the
+            // surface with no depth to find
+            return Ok(None);
+        }
+        // Fill a point, segment or triangle through the origin out into a
+        // tetrahedron. A tetrahedron with the origin on its boundary already
+        // encloses it.
+        while simplex.len() < 4 {
+            let Some(supp_point) = grow_sideways(&simplex, support) else {
+                // Flat around the origin, the shapes only touch
+                return Ok(None);
+            };
+            simplex.push(supp_point);
+        }
+        // Expand the simplex into a polytope until its face closest to the
+        // origin is on the surface of the minkowski difference
+        let mut points: Vec<Vec3> = simplex.iter().map(|s| s.p).collect();
+        if (points[1] - points[0]).dot((points[2] - points[0]).cross(points[3] - points[0])) > 0.0 {
+            // Wind the faces so their normals point outwards
+            points.swap(1, 2);
         }
-        // Check if simplex is a tetrahedron
-        // If it is, find the max penetration or separation
-        if supp_points.len() == 4 {
-            let mut points = supp_points.clone();
-            let points_len = points.len();
-            let mut face_pts: Vec<_> = (0..points_len)
-                .map(|i| [i, (i + 1) % points_len, (i + 2) % points_len])
+        let mut faces = [[0, 1, 2], [0, 3, 1], [0, 2, 3], [1, 3, 2]]
+            .into_iter()
+            .map(|idxs| EpaFace::new(&points, idxs))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut closest;
+        let mut iterations = 0;
+        loop {
+            closest = faces
+                .iter()
+                .min_by(|f1, f2| f1.dist.total_cmp(&f2.dist))
+                .ok_or(CollisionError::EmptyPolytope)?
+                .clone();
+            iterations += 1;
+            if iterations > EPA_MAX_ITERATIONS {
+                break;
+            }
+            let new_point = support(closest.normal).p;
+            if new_point.dot(closest.normal) - closest.dist <= EPA_PROGRESS_EPSILON {
+                break;
+            }
+            // Every face the new point can see goes, and the hole gets
+            // patched with faces from its horizon to the new point
+            let visible: Vec<bool> = faces
+                .iter()
+                .map(|face| face.normal.dot(new_point - points[face.idxs[0]]) > 0.0)
                 .collect();
-            let mut pen_dir;
-            let mut max_face_dist = f32::NEG_INFINITY;
-            let mut epa_cycles = 0;
-            'epa: loop {
-                epa_cycles += 1;
-                // Calculate tetrahedron faces
-                let mut faces = vec![];
-                for idxs in &face_pts {
-                    let mut tri_plane =
-                        get_triangle_plane(points[idxs[0]], points[idxs[1]], points[idxs[2]]);
-                    if tri_plane.w < 0.0 {
-                        tri_plane = -tri_plane;
+            let mut horizon: Vec<[usize; 2]> = vec![];
+            for face in faces.iter().zip(&visible).filter(|(_, v)| **v) {
+                for i in 0..3 {
+                    let edge = [face.0.idxs[i], face.0.idxs[(i + 1) % 3]];
+                    if let Some(pos) = horizon.iter().position(|e| *e == [edge[1], edge[0]]) {
+                        horizon.swap_remove(pos);
+                    } else {
+                        horizon.push(edge);
                     }
-                    faces.push(tri_plane);
-                }
-                // Find closest face
-                let mut c_min_dist_face_idx = 0;
-                for (i, face) in faces.iter().enumerate() {
-                    if face.w < faces[c_min_dist_face_idx].w {
-                        c_min_dist_face_idx = i;
-                    }
-                }
-                let new_dir = -faces[c_min_dist_face_idx].xyz();
-                let farth_point_a = a.farthest_point_along(new_dir);
-                let farth_point_b = b.farthest_point_along(-new_dir);
-                let new_point = farth_point_a - farth_point_b;
-                let face_dist = faces[c_min_dist_face_idx].w;
-                if face_dist - EPA_PROGRESS_EPSILON <= max_face_dist && epa_cycles > MIN_EPA_CYCLES
-                {
-                    pen_dir = new_dir;
-                    max_face_dist = face_dist;
-                    break 'epa;
-                } else {
-                    pen_dir = new_dir;
-                    max_face_dist = face_dist;
-                    for p in &points {
-                        if *p == new_point {
-                            break 'epa;
-                        }
-                    }
-                    points = vec![
-                        points[face_pts[c_min_dist_face_idx][0]],
-                        points[face_pts[c_min_dist_face_idx][1]],
-                        points[face_pts[c_min_dist_face_idx][2]],
-                        new_point,
-                    ];
-                    face_pts = vec![[0, 1, 3], [1, 2, 3], [0, 2, 3]];
                 }
             }
-            return Some(Self {
-                dir: pen_dir,
-                dist: max_face_dist,
-            });
-        } else {
+            points.push(new_point);
+            let new_idx = points.len() - 1;
+            let Ok(new_faces) = horizon
+                .iter()
+                .map(|edge| EpaFace::new(&points, [edge[0], edge[1], new_idx]))
+                .collect::<Result<Vec<_>, _>>()
+            else {
+                // New point is in line with the horizon, so it barely moves
+                // the surface out. The closest face is as good as it gets.
+                break;
+            };
+            let mut visible = visible.into_iter();
+            faces.retain(|_| !visible.next().unwrap_or(false));
+            faces.extend(new_faces);
+        }
+        *dir = closest.normal;
+        if closest.dist <= surface_tolerance(&simplex) {
+            // Origin was on the surface after all
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            dir: closest.normal,
+            dist: closest.dist,
+        }))
+    }
+
+    // Spheres and capsules overlapping only as far as their radii, found from
+    // how far apart their cores are. That is exact and much cheaper than EPA,
+    // which only creeps up on round surfaces. None if neither shape is round
+    // or their cores overlap too, EPA has to find the depth then.
+    fn new_with_cores(a: &CollisionShape, b: &CollisionShape) -> Option<Option<Self>> {
+        let (core_a, radius_a) = core_and_radius(a);
+        let (core_b, radius_b) = core_and_radius(b);
+        if radius_a + radius_b == 0.0 {
             return None;
         }
+        let cores = DistanceInfo::new_with_gjk(&core_a, &core_b)?;
+        let depth = radius_a + radius_b - cores.distance;
+        if depth <= GJK_EPSILON {
+            // Apart or only touching
+            return Some(None);
+        }
+        Some(Some(Self {
+            dir: cores.normal,
+            dist: depth,
+        }))
     }
 
     pub fn obj_swapped(mut self) -> Self {
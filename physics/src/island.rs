@@ -0,0 +1,26 @@
+// Union find over body indices, bodies touching each other end up in the
+// same island
+pub(crate) struct Islands {
+    parent: Vec<usize>,
+}
+
+impl Islands {
+    pub fn new(count: usize) -> Self {
+        Self {
+            parent: (0..count).collect(),
+        }
+    }
+
+    pub fn root(&mut self, mut i: usize) -> usize {
+        while self.parent[i] != i {
+            self.parent[i] = self.parent[self.parent[i]];
+            i = self.parent[i];
+        }
+        i
+    }
+
+    pub fn join(&mut self, a: usize, b: usize) {
+        let (ra, rb) = (self.root(a), self.root(b));
+        self.parent[rb] = ra;
+    }
+}
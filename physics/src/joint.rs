@@ -0,0 +1,339 @@
+use common::Entity;
+use glam::{Quat, Vec3};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::RigidBody;
+
+// Fraction of the position error fed back into the velocity each step
+const JOINT_BAUMGARTE: f32 = 0.2;
+
+// Drives a hinge or slider towards a relative speed, pushing with at most
+// max_force (a torque for hinges)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JointMotor {
+    pub target_velocity: f32,
+    pub max_force: f32,
+}
+
+impl JointMotor {
+    pub fn new(target_velocity: f32, max_force: f32) -> Self {
+        Self {
+            target_velocity,
+            max_force,
+        }
+    }
+}
+
+// Axes are kept in the local frame of body a. Limits are angles in radians for
+// hinges and distances along the axis for sliders.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum JointKind {
+    Fixed,
+    BallSocket,
+    Hinge {
+        axis: Vec3,
+        limits: Option<[f32; 2]>,
+        motor: Option<JointMotor>,
+    },
+    Prismatic {
+        axis: Vec3,
+        limits: Option<[f32; 2]>,
+        motor: Option<JointMotor>,
+    },
+    Distance {
+        min: f32,
+        max: f32,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Joint {
+    pub body_a: Entity,
+    pub body_b: Entity,
+    pub local_anchor_a: Vec3,
+    pub local_anchor_b: Vec3,
+    pub kind: JointKind,
+    // Rotation of b relative to a when the joint was made
+    rest_rotation: Quat,
+}
+
+impl Joint {
+    // Anchors and axes are in world space, taken from where the bodies are now
+    pub fn new(
+        rigid_bodies: &IndexMap<Entity, RigidBody>,
+        body_a: Entity,
+        body_b: Entity,
+        anchor_a: Vec3,
+        anchor_b: Vec3,
+        kind: JointKind,
+    ) -> anyhow::Result<Self> {
+        if body_a == body_b {
+            anyhow::bail!("joint connects {body_a:?} to itself");
+        }
+        let Some(rb_a) = rigid_bodies.get(&body_a) else {
+            anyhow::bail!("joint body {body_a:?} doesn't exist");
+        };
+        let Some(rb_b) = rigid_bodies.get(&body_b) else {
+            anyhow::bail!("joint body {body_b:?} doesn't exist");
+        };
+        let rot_a = rb_a.rotation();
+        let to_local_axis = |axis: Vec3| -> anyhow::Result<Vec3> {
+            let Some(axis) = axis.try_normalize() else {
+                anyhow::bail!("joint axis can't be zero");
+            };
+            Ok(rot_a.inverse() * axis)
+        };
+        // Crossed ranges would make the solver push both ways every step
+        let check_range = |lo: f32, hi: f32| {
+            if lo > hi {
+                anyhow::bail!("joint range {lo} to {hi} is the wrong way round");
+            }
+            Ok(())
+        };
+        let check = |limits: &Option<[f32; 2]>, motor: &Option<JointMotor>| {
+            if let Some([lo, hi]) = *limits {
+                check_range(lo, hi)?;
+            }
+            if let Some(motor) = motor
+                && motor.max_force < 0.0
+            {
+                anyhow::bail!("joint motor max force {} is negative", motor.max_force);
+            }
+            Ok(())
+        };
+        let kind = match kind {
+            JointKind::Hinge {
+                axis,
+                limits,
+                motor,
+            } => {
+                check(&limits, &motor)?;
+                JointKind::Hinge {
+                    axis: to_local_axis(axis)?,
+                    limits,
+                    motor,
+                }
+            }
+            JointKind::Prismatic {
+                axis,
+                limits,
+                motor,
+            } => {
+                check(&limits, &motor)?;
+                JointKind::Prismatic {
+                    axis: to_local_axis(axis)?,
+                    limits,
+                    motor,
+                }
+            }
+            JointKind::Distance { min, max } => {
+                check_range(min, max)?;
+                JointKind::Distance { min, max }
+            }
+            kind => kind,
+        };
+        Ok(Self {
+            body_a,
+            body_b,
+            local_anchor_a: rb_a
+                .orient
+                .to_transform()
+                .inverse()
+                .transform_point3(anchor_a),
+            local_anchor_b: rb_b
+                .orient
+                .to_transform()
+                .inverse()
+                .transform_point3(anchor_b),
+            kind,
+            rest_rotation: rot_a.inverse() * rb_b.rotation(),
+        })
+    }
+}
+
+// One scalar velocity constraint between two bodies. lin is pushed through
+// the anchors and ang is a pure relative rotation axis.
+struct JointRow {
+    lin: Vec3,
+    ang_a: Vec3,
+    ang_b: Vec3,
+    mass: f32,
+    target_vel: f32,
+    min_impulse: f32,
+    max_impulse: f32,
+    impulse: f32,
+}
+
+pub(crate) struct JointConstraint {
+    a: usize,
+    b: usize,
+    rows: Vec<JointRow>,
+}
+
+impl JointConstraint {
+    pub fn new(rigid_bodies: &IndexMap<Entity, RigidBody>, joint: &Joint, dt: f32) -> Option<Self> {
+        let a = rigid_bodies.get_index_of(&joint.body_a)?;
+        let b = rigid_bodies.get_index_of(&joint.body_b)?;
+        let (rb_a, rb_b) = (&rigid_bodies[a], &rigid_bodies[b]);
+        if rb_a.inv_mass() + rb_b.inv_mass() == 0.0 {
+            return None;
+        }
+        let anchor_a = rb_a
+            .orient
+            .to_transform()
+            .transform_point3(joint.local_anchor_a);
+        let anchor_b = rb_b
+            .orient
+            .to_transform()
+            .transform_point3(joint.local_anchor_b);
+        let r_a = anchor_a - rb_a.center_of_mass();
+        let r_b = anchor_b - rb_b.center_of_mass();
+        let d = anchor_b - anchor_a;
+        let rot_a = rb_a.rotation();
+        let rot_b = rb_b.rotation();
+        let (inv_inertia_a, inv_inertia_b) = (rb_a.inv_inertia_world(), rb_b.inv_inertia_world());
+        let bias = |error: f32| -JOINT_BAUMGARTE / dt * error;
+
+        let mut rows = vec![];
+        let mut push_row = |lin: Vec3, ang: Vec3, target_vel: f32, bounds: [f32; 2]| {
+            let ang_a = r_a.cross(lin) + ang;
+            let ang_b = r_b.cross(lin) + ang;
+            let k = lin.length_squared() * (rb_a.inv_mass() + rb_b.inv_mass())
+                + ang_a.dot(inv_inertia_a * ang_a)
+                + ang_b.dot(inv_inertia_b * ang_b);
+            if k <= f32::EPSILON {
+                return;
+            }
+            rows.push(JointRow {
+                lin,
+                ang_a,
+                ang_b,
+                mass: 1.0 / k,
+                target_vel,
+                min_impulse: bounds[0],
+                max_impulse: bounds[1],
+                impulse: 0.0,
+            });
+        };
+        let free = [f32::NEG_INFINITY, f32::INFINITY];
+        let lock_point = |push_row: &mut dyn FnMut(Vec3, Vec3, f32, [f32; 2]), dirs: &[Vec3]| {
+            for &dir in dirs {
+                push_row(dir, Vec3::ZERO, bias(d.dot(dir)), free);
+            }
+        };
+        let lock_rotation = |push_row: &mut dyn FnMut(Vec3, Vec3, f32, [f32; 2])| {
+            // Small angle rotation taking where b should be to where it is
+            let mut error = rot_b * (rot_a * joint.rest_rotation).inverse();
+            if error.w < 0.0 {
+                error = -error;
+            }
+            let error = 2.0 * error.xyz();
+            for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+                push_row(Vec3::ZERO, axis, bias(error.dot(axis)), free);
+            }
+        };
+        // Keeps the relative motion along lin / ang inside limits, and drives it
+        // with a motor
+        let limit_and_drive = |push_row: &mut dyn FnMut(Vec3, Vec3, f32, [f32; 2]),
+                               lin: Vec3,
+                               ang: Vec3,
+                               pos: f32,
+                               limits: &Option<[f32; 2]>,
+                               motor: &Option<JointMotor>| {
+            if let Some([lo, hi]) = *limits {
+                if lo == hi {
+                    push_row(lin, ang, bias(pos - lo), free);
+                } else if pos < lo {
+                    push_row(lin, ang, bias(pos - lo), [0.0, f32::INFINITY]);
+                } else if pos > hi {
+                    push_row(lin, ang, bias(pos - hi), [f32::NEG_INFINITY, 0.0]);
+                }
+            }
+            if let Some(motor) = motor {
+                let max = motor.max_force * dt;
+                push_row(lin, ang, motor.target_velocity, [-max, max]);
+            }
+        };
+
+        match &joint.kind {
+            JointKind::Fixed => {
+                lock_point(&mut push_row, &[Vec3::X, Vec3::Y, Vec3::Z]);
+                lock_rotation(&mut push_row);
+            }
+            JointKind::BallSocket => lock_point(&mut push_row, &[Vec3::X, Vec3::Y, Vec3::Z]),
+            JointKind::Hinge {
+                axis,
+                limits,
+                motor,
+            } => {
+                lock_point(&mut push_row, &[Vec3::X, Vec3::Y, Vec3::Z]);
+                let axis_a = rot_a * *axis;
+                let axis_b = rot_b * joint.rest_rotation.inverse() * *axis;
+                // Only let b turn around the hinge axis
+                let error = axis_a.cross(axis_b);
+                let perp_1 = axis_a.any_orthonormal_vector();
+                let perp_2 = axis_a.cross(perp_1);
+                for perp in [perp_1, perp_2] {
+                    push_row(Vec3::ZERO, perp, bias(error.dot(perp)), free);
+                }
+                // Angle between a reference direction carried by each body
+                let local_ref = axis.any_orthonormal_vector();
+                let ref_a = rot_a * local_ref;
+                let ref_b = rot_b * joint.rest_rotation.inverse() * local_ref;
+                let angle = axis_a.dot(ref_a.cross(ref_b)).atan2(ref_a.dot(ref_b));
+                limit_and_drive(&mut push_row, Vec3::ZERO, axis_a, angle, limits, motor);
+            }
+            JointKind::Prismatic {
+                axis,
+                limits,
+                motor,
+            } => {
+                let axis_a = rot_a * *axis;
+                let perp_1 = axis_a.any_orthonormal_vector();
+                let perp_2 = axis_a.cross(perp_1);
+                lock_point(&mut push_row, &[perp_1, perp_2]);
+                lock_rotation(&mut push_row);
+                limit_and_drive(
+                    &mut push_row,
+                    axis_a,
+                    Vec3::ZERO,
+                    d.dot(axis_a),
+                    limits,
+                    motor,
+                );
+            }
+            JointKind::Distance { min, max } => {
+                let len = d.length();
+                if let Some(dir) = d.try_normalize() {
+                    limit_and_drive(
+                        &mut push_row,
+                        dir,
+                        Vec3::ZERO,
+                        len,
+                        &Some([*min, *max]),
+                        &None,
+                    );
+                }
+            }
+        }
+        Some(Self { a, b, rows })
+    }
+
+    pub fn solve(&mut self, rigid_bodies: &mut IndexMap<Entity, RigidBody>) {
+        for row in self.rows.iter_mut() {
+            let (rb_a, rb_b) = (&rigid_bodies[self.a], &rigid_bodies[self.b]);
+            let vel = row
+                .lin
+                .dot(rb_b.kinematics.velocity - rb_a.kinematics.velocity)
+                + row.ang_b.dot(rb_b.kinematics.angular_velocity)
+                - row.ang_a.dot(rb_a.kinematics.angular_velocity);
+            let lambda = (row.target_vel - vel) * row.mass;
+            let new_impulse = (row.impulse + lambda).clamp(row.min_impulse, row.max_impulse);
+            let delta = new_impulse - row.impulse;
+            row.impulse = new_impulse;
+            rigid_bodies[self.a].apply_joint_impulse(-row.lin * delta, -row.ang_a * delta);
+            rigid_bodies[self.b].apply_joint_impulse(row.lin * delta, row.ang_b * delta);
+        }
+    }
+}
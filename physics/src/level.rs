@@ -0,0 +1,195 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Context;
+use common::{Entity, Level, Node, PhysicsJoint, PhysicsRb, Shape};
+use glam::{Mat4, Vec3};
+use indexmap::IndexMap;
+
+use crate::{
+    BodyType, CollisionFilter, Kinematics, Material, Orientation, PhysicsConfig, PhysicsManager,
+    RigidBody,
+    collision_shape::CollisionShape,
+    joint::{Joint, JointKind, JointMotor},
+    mass::DEFAULT_DENSITY,
+};
+
+impl CollisionShape {
+    pub fn from_level(shape: &Shape) -> anyhow::Result<Self> {
+        let shape = match shape {
+            Shape::Rectangle { c, x, y } => Self::new_rect(
+                Vec3::from_array(*c),
+                Vec3::from_array(*x),
+                Vec3::from_array(*y),
+            ),
+            Shape::Cube { c, x, y, h } => Self::new_cube(
+                Vec3::from_array(*c),
+                Vec3::from_array(*x),
+                Vec3::from_array(*y),
+                *h,
+            ),
+            Shape::Sphere { c, r } => Self::new_sphere(Vec3::from_array(*c), *r),
+            Shape::Capsule { a, b, r } => {
+                Self::new_capsule(Vec3::from_array(*a), Vec3::from_array(*b), *r)
+            }
+            // Parts are already placed by their own centers
+            Shape::Compound { parts } => Self::new_compound(
+                parts
+                    .iter()
+                    .map(|p| {
+                        if let Shape::TriangleMesh { .. } = p {
+                            anyhow::bail!("compound part can't be a triangle mesh");
+                        }
+                        Ok((Orientation::new(), Self::from_level(p)?))
+                    })
+                    .collect::<anyhow::Result<_>>()?,
+            )?,
+            Shape::TriangleMesh { vertices, indices } => {
+                if vertices.len() > u16::MAX as usize + 1 {
+                    anyhow::bail!("triangle mesh has too many vertices: {}", vertices.len());
+                }
+                let vertices: Vec<_> = vertices.iter().map(|v| Vec3::from_array(*v)).collect();
+                Self::new_triangle_mesh(&vertices, indices)?
+            }
+        };
+        Ok(shape)
+    }
+}
+
+impl RigidBody {
+    pub fn from_level(rb: &PhysicsRb) -> anyhow::Result<Self> {
+        let shape = CollisionShape::from_level(&rb.shape)?;
+        let orient = Orientation {
+            translation: Vec3::from_array(rb.init_location),
+            rotation: Mat4::IDENTITY,
+        };
+        let body_type = match (rb.body_type, rb.mass) {
+            (Some(common::BodyType::Static), _) => BodyType::Static,
+            (Some(common::BodyType::Kinematic), _) => BodyType::Kinematic,
+            (Some(common::BodyType::Dynamic), f32::INFINITY) => {
+                anyhow::bail!("dynamic body can't have an infinite mass")
+            }
+            (Some(common::BodyType::Dynamic), _) => BodyType::Dynamic,
+            (None, f32::INFINITY) => BodyType::Static,
+            (None, _) => BodyType::Dynamic,
+        };
+        let mass_props = match (rb.mass, rb.density) {
+            (0.0, Some(density)) if !(density.is_finite() && density > 0.0) => {
+                anyhow::bail!("body density has to be positive and finite, got {density}")
+            }
+            // Flat shapes only get weighed by area, which a density per
+            // volume doesn't fit
+            (0.0, Some(_)) if shape.volume() <= f32::EPSILON => {
+                anyhow::bail!("flat body can't take a density, give it a mass instead")
+            }
+            (0.0, Some(density)) => shape.mass_properties(density),
+            (_, Some(_)) => anyhow::bail!("body has both a mass and a density"),
+            (0.0 | f32::INFINITY, None) => shape.mass_properties(DEFAULT_DENSITY),
+            (mass, None) if mass > 0.0 => shape.mass_properties(DEFAULT_DENSITY).with_mass(mass),
+            (mass, None) => anyhow::bail!("body mass has to be positive, got {mass}"),
+        };
+        let mut rigid_body = Self::new(
+            body_type,
+            Arc::new(shape),
+            orient,
+            Kinematics::new(),
+            rb.can_rotate,
+            rb.gravity_scale,
+            CollisionFilter::new(rb.category, rb.ignore),
+        );
+        rigid_body.material = Material {
+            restitution: rb.material.restitution,
+            static_friction: rb.material.static_friction,
+            dynamic_friction: rb.material.dynamic_friction,
+        };
+        rigid_body.set_mass_properties(mass_props);
+        rigid_body.ccd = rb.ccd;
+        rigid_body.is_sensor = rb.is_sensor;
+        Ok(rigid_body)
+    }
+}
+
+impl Joint {
+    pub fn from_level(
+        joint: &PhysicsJoint,
+        named: &HashMap<&str, Entity>,
+        rigid_bodies: &IndexMap<Entity, RigidBody>,
+    ) -> anyhow::Result<Self> {
+        let lookup = |name: &str| {
+            named
+                .get(name)
+                .copied()
+                .with_context(|| format!("joint body {name} not found"))
+        };
+        let motor = |motor: &Option<common::JointMotor>| {
+            motor
+                .as_ref()
+                .map(|m| JointMotor::new(m.target_velocity, m.max_force))
+        };
+        let kind = match &joint.kind {
+            common::JointKind::Fixed => JointKind::Fixed,
+            common::JointKind::BallSocket => JointKind::BallSocket,
+            common::JointKind::Hinge {
+                axis,
+                limits,
+                motor: m,
+            } => JointKind::Hinge {
+                axis: Vec3::from_array(*axis),
+                limits: *limits,
+                motor: motor(m),
+            },
+            common::JointKind::Prismatic {
+                axis,
+                limits,
+                motor: m,
+            } => JointKind::Prismatic {
+                axis: Vec3::from_array(*axis),
+                limits: *limits,
+                motor: motor(m),
+            },
+            common::JointKind::Distance { min, max } => JointKind::Distance {
+                min: *min,
+                max: *max,
+            },
+        };
+        let anchor_a = Vec3::from_array(joint.anchor_a);
+        Self::new(
+            rigid_bodies,
+            lookup(&joint.body_a)?,
+            lookup(&joint.body_b)?,
+            anchor_a,
+            joint.anchor_b.map(Vec3::from_array).unwrap_or(anchor_a),
+            kind,
+        )
+    }
+}
+
+impl PhysicsManager {
+    // Bodies get entities numbered from 0 in the order they show up in the
+    // level
+    pub fn from_level(level: &Level, config: PhysicsConfig) -> anyhow::Result<Self> {
+        let mut physics = Self::with_config(config);
+        let mut named = HashMap::new();
+        let mut joint_nodes = vec![];
+        let mut next_ent_id = 0;
+        for node in &level.nodes {
+            match node {
+                Node::PhysicsRb(physics_rb) => {
+                    let ent = Entity::new(next_ent_id);
+                    physics.add_body(ent, RigidBody::from_level(physics_rb)?)?;
+                    if let Some(name) = &physics_rb.name {
+                        named.insert(name.as_str(), ent);
+                    }
+                    next_ent_id += 1;
+                }
+                // Bodies can come after their joints, so these wait until all
+                // of them exist
+                Node::Joint(joint) => joint_nodes.push(joint),
+            }
+        }
+        physics.joints = joint_nodes
+            .into_iter()
+            .map(|joint| Joint::from_level(joint, &named, &physics.rigid_bodies))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(physics)
+    }
+}
@@ -1,21 +1,70 @@
 use std::sync::Arc;
 
 use common::Entity;
-use glam::Mat4;
-use indexmap::IndexMap;
+use glam::{Mat3, Mat4, Quat, Vec3};
+use hashbrown::HashSet;
+use indexmap::{IndexMap, IndexSet};
+use serde::{Deserialize, Serialize};
 
-use crate::{collision_shape::CollisionShape, intersection_info::IntersectionInfo};
+use crate::{
+    aabb::Aabb,
+    broad_phase::SweepAndPrune,
+    collision_shape::CollisionShape,
+    contact_manifold::ContactManifold,
+    contact_solver::{ContactSolver, pair_key},
+    event::PhysicsEvent,
+    handle::{BodyHandle, BodySlots},
+    intersection_info::IntersectionInfo,
+    island::Islands,
+    joint::Joint,
+    mass::{DEFAULT_DENSITY, MassProperties},
+    pair_cache::PairCache,
+    snapshot::RigidBodyState,
+};
 
+pub mod aabb;
+mod broad_phase;
+mod bvh;
+pub mod character;
 pub mod collision_shape;
+pub mod contact_manifold;
+mod contact_solver;
+mod convex_hull;
+pub mod debug_draw;
+pub mod distance;
+pub mod error;
+pub mod event;
+pub mod handle;
 pub mod intersection_info;
+mod island;
+pub mod joint;
+mod level;
+pub mod mass;
+mod pair_cache;
+pub mod query;
+mod simplex;
+pub mod snapshot;
 mod utils;
 
-#[derive(Debug, Clone)]
+// Overlap allowed to remain after pushing bodies apart
+const PENETRATION_SLOP: f32 = 0.005;
+// Kinetic energy per unit of mass below which a body counts as resting
+const SLEEP_ENERGY_THRESHOLD: f32 = 0.02;
+// Seconds a whole island has to rest before it goes to sleep
+const TIME_TO_SLEEP: f32 = 0.5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Orientation {
     pub translation: glam::Vec3,
     pub rotation: glam::Mat4,
 }
 
+impl Default for Orientation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Orientation {
     pub fn new() -> Self {
         Self {
@@ -38,10 +87,17 @@ impl Orientation {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Kinematics {
     pub velocity: glam::Vec3,
     pub acceleration: glam::Vec3,
+    pub angular_velocity: glam::Vec3,
+}
+
+impl Default for Kinematics {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Kinematics {
@@ -49,60 +105,410 @@ impl Kinematics {
         Self {
             velocity: glam::Vec3::ZERO,
             acceleration: glam::Vec3::ZERO,
+            angular_velocity: glam::Vec3::ZERO,
         }
     }
 }
 
-#[derive(Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Material {
+    pub restitution: f32,
+    pub static_friction: f32,
+    pub dynamic_friction: f32,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Material {
+    pub fn new() -> Self {
+        Self {
+            restitution: 0.0,
+            static_friction: 0.6,
+            dynamic_friction: 0.4,
+        }
+    }
+
+    pub fn combine(&self, other: &Self) -> Self {
+        Self {
+            restitution: self.restitution.max(other.restitution),
+            static_friction: (self.static_friction * other.static_friction).sqrt(),
+            dynamic_friction: (self.dynamic_friction * other.dynamic_friction).sqrt(),
+        }
+    }
+}
+
+// Bodies collide unless one's category is in the other's ignore mask
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CollisionFilter {
+    pub category: u32,
+    pub ignore: u32,
+}
+
+impl Default for CollisionFilter {
+    fn default() -> Self {
+        Self::new(1, 0)
+    }
+}
+
+impl CollisionFilter {
+    pub fn new(category: u32, ignore: u32) -> Self {
+        Self { category, ignore }
+    }
+
+    pub fn can_interact(&self, other: &Self) -> bool {
+        self.category & other.ignore == 0 && other.category & self.ignore == 0
+    }
+}
+
+// Static bodies never move, kinematic ones move only by the velocity they're
+// given. Neither gets pushed around by anything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BodyType {
+    Static,
+    Kinematic,
+    Dynamic,
+}
+
+// The oriented shape and its bounds are rebuilt when loaded, see snapshot.rs
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "RigidBodyState", into = "RigidBodyState")]
 pub struct RigidBody {
-    pub mass: f32,
+    pub body_type: BodyType,
     pub shape: Arc<CollisionShape>,
     pub orient: Orientation,
     orient_shape: CollisionShape,
+    prev_orient: Orientation,
+    aabb: Aabb,
     pub kinematics: Kinematics,
+    pub material: Material,
     pub can_rotate: bool,
-    pub has_gravity: bool,
-    pub dont_interact_mask: u32,
-    stuck: bool,
+    pub gravity_scale: f32,
+    pub filter: CollisionFilter,
+    // Sweeps the body along its motion each step so it can't tunnel through
+    // thin or fast approaching bodies
+    pub ccd: bool,
+    // Sensors report overlaps as events but never push or get pushed
+    pub is_sensor: bool,
+    mass_props: MassProperties,
+    inv_inertia: Mat3,
+    force: Vec3,
+    torque: Vec3,
+    impulse: Vec3,
+    angular_impulse: Vec3,
+    sleeping: bool,
+    rest_time: f32,
 }
 
 impl RigidBody {
+    // Mass comes from the shape at DEFAULT_DENSITY, see set_mass_properties to
+    // change it
     pub fn new(
-        mass: f32,
+        body_type: BodyType,
         shape: Arc<CollisionShape>,
         initial_orient: Orientation,
         initial_kin: Kinematics,
         can_rotate: bool,
-        has_gravity: bool,
-        dont_interact_mask: u32,
+        gravity_scale: f32,
+        filter: CollisionFilter,
     ) -> Self {
+        // Triangle meshes are open surfaces, they can only be static
+        let body_type = if let CollisionShape::TriangleMesh(_) = shape.as_ref() {
+            BodyType::Static
+        } else {
+            body_type
+        };
         let orient_shape = shape.with_orientation(&initial_orient);
-        Self {
-            mass,
+        let aabb = orient_shape.aabb();
+        let mass_props = shape.mass_properties(DEFAULT_DENSITY);
+        let mut rigid_body = Self {
+            body_type,
             shape,
+            prev_orient: initial_orient.clone(),
             orient: initial_orient,
             orient_shape,
+            aabb,
             kinematics: initial_kin,
+            material: Material::new(),
             can_rotate,
-            has_gravity,
-            dont_interact_mask,
-            stuck: false,
+            gravity_scale,
+            filter,
+            ccd: false,
+            is_sensor: false,
+            mass_props: mass_props.clone(),
+            inv_inertia: Mat3::ZERO,
+            force: Vec3::ZERO,
+            torque: Vec3::ZERO,
+            impulse: Vec3::ZERO,
+            angular_impulse: Vec3::ZERO,
+            sleeping: false,
+            rest_time: 0.0,
+        };
+        rigid_body.set_mass_properties(mass_props);
+        rigid_body
+    }
+
+    pub fn mass_properties(&self) -> &MassProperties {
+        &self.mass_props
+    }
+
+    // Usually the shape's own, from CollisionShape::mass_properties with a
+    // density or scaled to a mass with MassProperties::with_mass
+    pub fn set_mass_properties(&mut self, mass_props: MassProperties) {
+        let inertia = mass_props.inertia;
+        // Compare against the tensor's own scale, small bodies have tiny
+        // determinants but still rotate
+        let scale = inertia.x_axis.x + inertia.y_axis.y + inertia.z_axis.z;
+        self.inv_inertia = if inertia.determinant() > f32::EPSILON * scale.powi(3) {
+            inertia.inverse()
+        } else {
+            Mat3::ZERO
+        };
+        self.mass_props = mass_props;
+    }
+
+    // Static and kinematic bodies can't be moved by anything, so they weigh
+    // infinitely much as far as the solver is concerned
+    pub fn mass(&self) -> f32 {
+        match self.body_type {
+            BodyType::Dynamic => self.mass_props.mass,
+            BodyType::Static | BodyType::Kinematic => f32::INFINITY,
         }
     }
 
-    pub fn is_stuck(&self) -> bool {
-        self.stuck
+    pub fn is_sleeping(&self) -> bool {
+        self.sleeping
     }
 
-    pub fn fwd_ms(&mut self) {
-        self.orient.translation +=
-            self.kinematics.velocity * 0.001 + 0.5 * self.kinematics.acceleration * 0.001 * 0.001;
-        self.kinematics.velocity += self.kinematics.acceleration * 0.001;
+    pub fn wake_up(&mut self) {
+        self.sleeping = false;
+        self.rest_time = 0.0;
+    }
+
+    fn sleep(&mut self) {
+        self.sleeping = true;
+        self.kinematics.velocity = Vec3::ZERO;
+        self.kinematics.angular_velocity = Vec3::ZERO;
+    }
+
+    // Awake bodies that can push others. Static bodies can't, and neither can
+    // sleeping ones until something wakes them.
+    fn is_active(&self) -> bool {
+        !self.sleeping
+            && (self.inv_mass() > 0.0
+                || self.kinematics.velocity != Vec3::ZERO
+                || self.kinematics.angular_velocity != Vec3::ZERO)
+    }
+
+    fn overlaps(&self, other: &RigidBody) -> bool {
+        self.orient_shape
+            .part_pairs(&other.orient_shape)
+            .into_iter()
+            // Failing to find the depth still means the origin was enclosed
+            .any(|(_, a, b)| !matches!(IntersectionInfo::new_with_gjk(&a, &b), Ok(None)))
+    }
+
+    fn kinetic_energy_per_mass(&self) -> f32 {
+        let ang_vel = self.kinematics.angular_velocity;
+        let angular = ang_vel.dot(self.inertia_world() * ang_vel) * self.inv_mass();
+        0.5 * (self.kinematics.velocity.length_squared() + angular)
+    }
+
+    pub fn aabb(&self) -> &Aabb {
+        &self.aabb
+    }
+
+    // Covers the body wherever it can get to in a step from its current
+    // velocity plus what gravity and the accumulated forces add. Contacts can
+    // still fling it further, those get picked up the step after.
+    pub(crate) fn step_bounds(&self, dt: f32, gravity: Vec3) -> Aabb {
+        let inv_mass = self.inv_mass();
+        let mut gained =
+            (self.kinematics.acceleration + self.force * inv_mass) * dt + self.impulse * inv_mass;
+        if inv_mass > 0.0 {
+            gained += gravity * self.gravity_scale * dt;
+        }
+        // Moving either way, bounces can turn the body around
+        let travel = (self.kinematics.velocity.length() + gained.length()) * dt;
+        let spin = if self.can_rotate {
+            self.kinematics.angular_velocity.length()
+                * dt
+                * (self.aabb.max - self.aabb.min).length()
+                / 2.0
+        } else {
+            0.0
+        };
+        self.aabb.expanded(travel + spin)
+    }
+
+    // Forces and impulses are accumulated and applied on the next step
+    pub fn apply_force(&mut self, force: Vec3) {
+        self.wake_up();
+        self.force += force;
+    }
+
+    pub fn apply_force_at_point(&mut self, force: Vec3, point: Vec3) {
+        self.wake_up();
+        self.force += force;
+        self.torque += (point - self.center_of_mass()).cross(force);
+    }
+
+    pub fn apply_torque(&mut self, torque: Vec3) {
+        self.wake_up();
+        self.torque += torque;
+    }
+
+    pub fn apply_impulse(&mut self, impulse: Vec3) {
+        self.wake_up();
+        self.impulse += impulse;
+    }
+
+    pub fn apply_impulse_at_point(&mut self, impulse: Vec3, point: Vec3) {
+        self.wake_up();
+        self.impulse += impulse;
+        self.angular_impulse += (point - self.center_of_mass()).cross(impulse);
+    }
+
+    fn clear_accumulators(&mut self) {
+        self.force = Vec3::ZERO;
+        self.torque = Vec3::ZERO;
+        self.impulse = Vec3::ZERO;
+        self.angular_impulse = Vec3::ZERO;
+    }
+
+    fn rotation(&self) -> Quat {
+        Quat::from_mat4(&self.orient.rotation).normalize()
+    }
+
+    fn rotation_mat3(&self) -> Mat3 {
+        Mat3::from_mat4(self.orient.rotation)
+    }
+
+    pub fn inertia_world(&self) -> Mat3 {
+        let rot = self.rotation_mat3();
+        rot * self.mass_props.inertia * rot.transpose()
+    }
+
+    // can_rotate is checked here rather than baked into inv_inertia, so it
+    // can be changed at any time
+    pub fn inv_inertia_world(&self) -> Mat3 {
+        if self.body_type != BodyType::Dynamic || !self.can_rotate {
+            return Mat3::ZERO;
+        }
+        let rot = self.rotation_mat3();
+        rot * self.inv_inertia * rot.transpose()
+    }
+
+    pub fn inv_mass(&self) -> f32 {
+        match self.body_type {
+            BodyType::Dynamic if self.mass_props.mass > 0.0 => 1.0 / self.mass_props.mass,
+            _ => 0.0,
+        }
+    }
+
+    pub fn center_of_mass(&self) -> Vec3 {
+        self.orient
+            .to_transform()
+            .transform_point3(self.mass_props.center)
+    }
+
+    pub fn velocity_at(&self, point: Vec3) -> Vec3 {
+        self.kinematics.velocity
+            + self
+                .kinematics
+                .angular_velocity
+                .cross(point - self.center_of_mass())
+    }
+
+    pub(crate) fn apply_velocity_impulse(&mut self, impulse: Vec3, rel_pos: Vec3) {
+        self.kinematics.velocity += impulse * self.inv_mass();
+        self.kinematics.angular_velocity += self.inv_inertia_world() * rel_pos.cross(impulse);
+    }
+
+    pub(crate) fn apply_joint_impulse(&mut self, impulse: Vec3, angular_impulse: Vec3) {
+        self.kinematics.velocity += impulse * self.inv_mass();
+        self.kinematics.angular_velocity += self.inv_inertia_world() * angular_impulse;
+    }
+
+    // Blend between the previous and current step for smooth rendering
+    pub fn interpolated_transform(&self, alpha: f32) -> Mat4 {
+        let translation = self
+            .prev_orient
+            .translation
+            .lerp(self.orient.translation, alpha);
+        let rotation = Quat::from_mat4(&self.prev_orient.rotation)
+            .slerp(Quat::from_mat4(&self.orient.rotation), alpha);
+        Mat4::from_rotation_translation(rotation, translation)
+    }
+
+    fn integrate_velocity(&mut self, dt: f32, gravity: Vec3) {
+        let inv_mass = self.inv_mass();
+        let mut acceleration = self.kinematics.acceleration + self.force * inv_mass;
+        if inv_mass > 0.0 {
+            acceleration += gravity * self.gravity_scale;
+        }
+        self.kinematics.velocity += acceleration * dt + self.impulse * inv_mass;
+        if self.can_rotate {
+            let ang_vel = self.kinematics.angular_velocity;
+            let gyro = ang_vel.cross(self.inertia_world() * ang_vel);
+            self.kinematics.angular_velocity +=
+                self.inv_inertia_world() * ((self.torque - gyro) * dt + self.angular_impulse);
+        }
+        self.clear_accumulators();
+    }
+
+    // Moves the body only up to where it hits something, going just past it
+    // so the contact gets picked up next step. The speed into the surface is
+    // dropped too, otherwise the body carries on through it next step.
+    fn integrate_position_to_impact(&mut self, dt: f32, fraction: f32, normal: Vec3) {
+        let motion = self.kinematics.velocity * dt;
+        let travel = (motion.length() * fraction + PENETRATION_SLOP).min(motion.length());
+        self.orient.translation += motion.normalize_or_zero() * travel;
+        let approach = self.kinematics.velocity.dot(normal);
+        if approach < 0.0 {
+            self.kinematics.velocity -= normal * approach;
+        }
+        if self.can_rotate {
+            self.integrate_rotation(dt);
+        }
+        self.refresh_orient_shape();
+    }
+
+    fn integrate_position(&mut self, dt: f32) {
+        if self.kinematics.velocity == Vec3::ZERO && self.kinematics.angular_velocity == Vec3::ZERO
+        {
+            // Resting and static bodies keep their shapes as they are
+            return;
+        }
+        self.orient.translation += self.kinematics.velocity * dt;
+        if self.can_rotate {
+            self.integrate_rotation(dt);
+        }
         self.refresh_orient_shape();
     }
 
+    fn integrate_rotation(&mut self, dt: f32) {
+        let ang_vel = self.kinematics.angular_velocity;
+        let angle = ang_vel.length() * dt;
+        if angle == 0.0 {
+            return;
+        }
+        // Rotate about the center of mass instead of the local origin
+        let local_center = self.mass_props.center;
+        let old_center = self.orient.rotation.transform_vector3(local_center);
+        let rot = Quat::from_axis_angle(ang_vel.normalize(), angle)
+            * Quat::from_mat4(&self.orient.rotation);
+        self.orient.rotation = Mat4::from_quat(rot.normalize());
+        let new_center = self.orient.rotation.transform_vector3(local_center);
+        self.orient.translation += old_center - new_center;
+    }
+
     fn refresh_orient_shape(&mut self) {
         self.orient_shape = self.shape.with_orientation(&self.orient);
+        self.aabb = self.orient_shape.aabb();
     }
 
     fn apply_orient(&mut self, orientation: &Orientation) {
@@ -112,109 +518,599 @@ impl RigidBody {
     }
 }
 
-pub struct PhysicsScene {
-    pub rigid_bodies: Vec<RigidBody>,
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhysicsConfig {
+    pub dt: f32,
+    pub max_substeps: u32,
+    pub gravity: Vec3,
+}
+
+impl Default for PhysicsConfig {
+    fn default() -> Self {
+        Self {
+            dt: 1.0 / 240.0,
+            max_substeps: 8,
+            gravity: Vec3::new(0.0, -10.0, 0.0),
+        }
+    }
+}
+
+impl PhysicsConfig {
+    // Falls back to the defaults for a dt that isn't a positive number or a
+    // gravity that isn't finite, and always allows at least one substep so
+    // update keeps stepping
+    pub fn validated(self) -> Self {
+        let default = Self::default();
+        let dt = if self.dt.is_finite() && self.dt > 0.0 {
+            self.dt
+        } else {
+            log::warn!("invalid step time {}, using {}", self.dt, default.dt);
+            default.dt
+        };
+        let max_substeps = if self.max_substeps > 0 {
+            self.max_substeps
+        } else {
+            log::warn!("max_substeps is 0, using 1");
+            1
+        };
+        let gravity = if self.gravity.is_finite() {
+            self.gravity
+        } else {
+            log::warn!(
+                "invalid gravity {}, using {}",
+                self.gravity,
+                default.gravity
+            );
+            default.gravity
+        };
+        Self {
+            dt,
+            max_substeps,
+            gravity,
+        }
+    }
+}
+
+// Manifold between bodies i and j, one for each touching pair of their parts
+type Touch = (usize, usize, ContactManifold);
+
+// Work done by the last step, for profiling
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StepStats {
+    pub awake_bodies: usize,
+    // Body pairs that went through the narrow phase
+    pub pair_tests: usize,
+    // Part pairs found touching and the contact points between them
+    pub touches: usize,
+    pub contact_points: usize,
 }
 
-pub struct PhysicsManager {}
+pub struct PhysicsManager {
+    pub config: PhysicsConfig,
+    pub joints: Vec<Joint>,
+    rigid_bodies: IndexMap<Entity, RigidBody>,
+    slots: BodySlots,
+    accumulator: f32,
+    broad_phase: SweepAndPrune,
+    pair_caches: IndexMap<(Entity, Entity), PairCache>,
+    events: Vec<PhysicsEvent>,
+    // Entity pairs touching and inside sensors after the last step
+    touching: IndexSet<(Entity, Entity)>,
+    sensed: IndexSet<(Entity, Entity)>,
+    // Manifolds the last step solved, kept around for debug drawing
+    contacts: Vec<(Entity, Entity, ContactManifold)>,
+    // Pairs the last step left resting on each other, to check the next one
+    // finds their contacts
+    resting: Vec<(Entity, Entity)>,
+    stats: StepStats,
+}
+
+impl Default for PhysicsManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl PhysicsManager {
     pub fn new() -> Self {
-        Self {}
-    }
-
-    fn resolve_penetrations(&mut self, rigid_bodies: &mut IndexMap<Entity, RigidBody>) {
-        let rb_count = rigid_bodies.len();
-        // Find penetrations
-        let mut touch_dirs = vec![vec![]; rb_count];
-        for i in 0..rb_count {
-            for j in i + 1..rb_count {
-                let Some(touch_info) = IntersectionInfo::new_with_gjk(
-                    &rigid_bodies[i].orient_shape,
-                    &rigid_bodies[j].orient_shape,
-                ) else {
-                    continue;
-                };
-                touch_dirs[i].push((j, touch_info));
-            }
-        }
-        // Resolve Penetrations
-        for a in 0..rb_count {
-            for (b, inter) in &touch_dirs[a] {
-                if inter.dist <= 0.0 {
-                    continue;
+        Self::with_config(PhysicsConfig::default())
+    }
+
+    pub fn with_config(config: PhysicsConfig) -> Self {
+        Self {
+            config: config.validated(),
+            joints: vec![],
+            rigid_bodies: IndexMap::new(),
+            slots: BodySlots::default(),
+            accumulator: 0.0,
+            broad_phase: SweepAndPrune::new(),
+            pair_caches: IndexMap::new(),
+            events: vec![],
+            touching: IndexSet::new(),
+            sensed: IndexSet::new(),
+            contacts: vec![],
+            resting: vec![],
+            stats: StepStats::default(),
+        }
+    }
+
+    pub fn add_body(&mut self, entity: Entity, rb: RigidBody) -> anyhow::Result<BodyHandle> {
+        if self.rigid_bodies.contains_key(&entity) {
+            anyhow::bail!("{entity:?} already has a body");
+        }
+        self.rigid_bodies.insert(entity, rb);
+        Ok(self.slots.insert())
+    }
+
+    // Also drops the joints and cached state the body was part of
+    pub fn remove_body(&mut self, handle: BodyHandle) -> Option<RigidBody> {
+        let index = self.slots.remove(handle)?;
+        let (entity, rb) = self.rigid_bodies.swap_remove_index(index)?;
+        // Whatever rested on or hung from the body has to notice it's gone
+        let partners: Vec<_> = self
+            .pair_caches
+            .keys()
+            .copied()
+            .chain(self.touching.iter().copied())
+            .chain(self.joints.iter().map(|joint| (joint.body_a, joint.body_b)))
+            .filter_map(|(a, b)| match (a == entity, b == entity) {
+                (true, _) => Some(b),
+                (_, true) => Some(a),
+                _ => None,
+            })
+            .collect();
+        for partner in partners {
+            if let Some(rb) = self.rigid_bodies.get_mut(&partner) {
+                rb.wake_up();
+            }
+        }
+        self.joints
+            .retain(|joint| joint.body_a != entity && joint.body_b != entity);
+        self.pair_caches
+            .retain(|(a, b), _| *a != entity && *b != entity);
+        self.touching.retain(|(a, b)| *a != entity && *b != entity);
+        self.sensed.retain(|(a, b)| *a != entity && *b != entity);
+        self.contacts
+            .retain(|(a, b, _)| *a != entity && *b != entity);
+        Some(rb)
+    }
+
+    // Removes every body and joint, keeping the config
+    pub fn clear(&mut self) {
+        *self = Self::with_config(self.config.clone());
+    }
+
+    pub fn handle_of(&self, entity: Entity) -> Option<BodyHandle> {
+        self.slots
+            .handle_of(self.rigid_bodies.get_index_of(&entity)?)
+    }
+
+    pub fn entity_of(&self, handle: BodyHandle) -> Option<Entity> {
+        let index = self.slots.get(handle)?;
+        Some(*self.rigid_bodies.get_index(index)?.0)
+    }
+
+    pub fn body(&self, handle: BodyHandle) -> Option<&RigidBody> {
+        self.rigid_bodies
+            .get_index(self.slots.get(handle)?)
+            .map(|(_, rb)| rb)
+    }
+
+    pub fn body_mut(&mut self, handle: BodyHandle) -> Option<&mut RigidBody> {
+        self.rigid_bodies
+            .get_index_mut(self.slots.get(handle)?)
+            .map(|(_, rb)| rb)
+    }
+
+    pub fn body_of(&self, entity: Entity) -> Option<&RigidBody> {
+        self.rigid_bodies.get(&entity)
+    }
+
+    pub fn body_of_mut(&mut self, entity: Entity) -> Option<&mut RigidBody> {
+        self.rigid_bodies.get_mut(&entity)
+    }
+
+    // Bodies in the order they are simulated
+    pub fn rigid_bodies(&self) -> &IndexMap<Entity, RigidBody> {
+        &self.rigid_bodies
+    }
+
+    pub fn step_stats(&self) -> &StepStats {
+        &self.stats
+    }
+
+    // How far the leftover time is into the next step, for interpolated_transform
+    pub fn interpolation_alpha(&self) -> f32 {
+        self.accumulator / self.config.dt
+    }
+
+    // Runs as many fixed steps as fit in the elapsed time, up to max_substeps.
+    // Leftover time carries over to the next call. Returns the steps run.
+    pub fn update(&mut self, elapsed: f32) -> u32 {
+        // Negative or broken frame times would push alpha out of [0, 1)
+        if elapsed.is_finite() && elapsed > 0.0 {
+            self.accumulator += elapsed;
+        }
+        let mut substeps = 0;
+        while self.accumulator >= self.config.dt && substeps < self.config.max_substeps {
+            self.step();
+            self.accumulator -= self.config.dt;
+            substeps += 1;
+        }
+        if self.accumulator >= self.config.dt {
+            // Fell too far behind, drop the time instead of spiralling
+            self.accumulator %= self.config.dt;
+        }
+        substeps
+    }
+
+    // Index pairs (i < j) of bodies connected by a joint
+    fn joined_pairs(&self) -> Vec<(usize, usize)> {
+        self.joints
+            .iter()
+            .filter_map(|joint| {
+                let a = self.rigid_bodies.get_index_of(&joint.body_a)?;
+                let b = self.rigid_bodies.get_index_of(&joint.body_b)?;
+                Some((a.min(b), a.max(b)))
+            })
+            .collect()
+    }
+
+    // Overlapping pairs that can actually push each other. Bodies joined
+    // together don't collide, the joint decides how they move.
+    // Pairs come sorted by index so the solver sees them in the same order
+    // every run, which keeps the simulation deterministic.
+    fn candidate_pairs(&self, overlaps: &[(usize, usize)]) -> Vec<(usize, usize)> {
+        let joined: HashSet<_> = self.joined_pairs().into_iter().collect();
+        let rigid_bodies = &self.rigid_bodies;
+        let mut pairs = overlaps.to_vec();
+        pairs.retain(|(i, j)| {
+            let (rb_a, rb_b) = (&rigid_bodies[*i], &rigid_bodies[*j]);
+            rb_a.inv_mass() + rb_b.inv_mass() > 0.0
+                && (rb_a.is_active() || rb_b.is_active())
+                && rb_a.filter.can_interact(&rb_b.filter)
+                && !rb_a.is_sensor
+                && !rb_b.is_sensor
+                && !joined.contains(&(*i, *j))
+        });
+        pairs
+    }
+
+    // Wakes sleeping bodies that got pushed
+    fn wake_pushed(&mut self) {
+        for rb in self.rigid_bodies.values_mut().filter(|rb| rb.sleeping) {
+            if rb.kinematics.velocity != Vec3::ZERO || rb.kinematics.angular_velocity != Vec3::ZERO
+            {
+                rb.wake_up();
+            }
+        }
+    }
+
+    // Wakes sleeping bodies joined to an active body. Repeats until nothing
+    // new wakes so whole chains wake together.
+    fn wake_joined(&mut self) {
+        let joined = self.joined_pairs();
+        let rigid_bodies = &mut self.rigid_bodies;
+        let mut woke_any = true;
+        while woke_any {
+            woke_any = false;
+            for &(i, j) in &joined {
+                let (rb_a, rb_b) = (&rigid_bodies[i], &rigid_bodies[j]);
+                if rb_a.sleeping && rb_b.is_active() {
+                    rigid_bodies[i].wake_up();
+                    woke_any = true;
+                } else if rb_b.sleeping && rb_a.is_active() {
+                    rigid_bodies[j].wake_up();
+                    woke_any = true;
                 }
-                let total_mass = rigid_bodies[a].mass + rigid_bodies[*b].mass;
-                let (a_move_dist, b_move_dist) = if total_mass != f32::INFINITY {
-                    let a_move_dist = -inter.dist * (rigid_bodies[a].mass / total_mass);
-                    let b_move_dist = inter.dist * (rigid_bodies[*b].mass / total_mass);
-                    (a_move_dist, b_move_dist)
-                } else {
-                    if rigid_bodies[a].mass == f32::INFINITY {
-                        (0., inter.dist)
-                    } else {
-                        (-inter.dist, 0.)
-                    }
-                };
-                rigid_bodies[a].apply_orient(&Orientation {
-                    translation: a_move_dist * inter.dir,
-                    rotation: Mat4::IDENTITY,
-                });
-                rigid_bodies[*b].apply_orient(&Orientation {
-                    translation: b_move_dist * inter.dir,
-                    rotation: Mat4::IDENTITY,
-                });
-            }
-        }
-    }
-
-    pub fn run_ms(&mut self, rigid_bodies: &mut IndexMap<Entity, RigidBody>) {
-        // resolve existing penetrations
-        self.resolve_penetrations(rigid_bodies);
-        // Find touches
-        let rb_count = rigid_bodies.len();
-        let mut touch_dirs = vec![vec![]; rb_count];
-        for i in 0..rb_count {
-            for j in i + 1..rb_count {
-                let Some(touch_info) = IntersectionInfo::new_with_gjk(
-                    &rigid_bodies[i].orient_shape,
-                    &rigid_bodies[j].orient_shape,
-                ) else {
-                    continue;
-                };
-                touch_dirs[i].push((j, touch_info.clone()));
-                touch_dirs[j].push((i, touch_info.obj_swapped()));
-            }
-        }
-        // Normalize velocities
-        for i in 0..rb_count {
-            if rigid_bodies[i].mass == f32::INFINITY {
+            }
+        }
+    }
+
+    // Puts islands of touching bodies to sleep once all of their bodies have
+    // been resting for long enough
+    fn update_sleep(&mut self, touches: &[(usize, usize, ContactManifold)]) {
+        let dt = self.config.dt;
+        let joined = self.joined_pairs();
+        let rigid_bodies = &mut self.rigid_bodies;
+        let mut islands = Islands::new(rigid_bodies.len());
+        let touching = touches.iter().map(|(i, j, _)| (*i, *j));
+        for (i, j) in touching.chain(joined) {
+            // Static bodies don't carry motion, so they don't join islands
+            if rigid_bodies[i].inv_mass() > 0.0 && rigid_bodies[j].inv_mass() > 0.0 {
+                islands.join(i, j);
+            }
+        }
+        let mut island_rest_time = vec![f32::INFINITY; rigid_bodies.len()];
+        for (i, rb) in rigid_bodies.values_mut().enumerate() {
+            if rb.sleeping || rb.inv_mass() == 0.0 {
                 continue;
             }
-            for (j, touch_info) in &touch_dirs[i] {
-                let rel_vel =
-                    rigid_bodies[i].kinematics.velocity - rigid_bodies[*j].kinematics.velocity;
-                let vel_component = rel_vel.dot(-touch_info.dir);
-                if vel_component < 0.0 {
-                    let blocked_vel = rel_vel - vel_component * -touch_info.dir;
-                    rigid_bodies[i].kinematics.velocity =
-                        blocked_vel + rigid_bodies[*j].kinematics.velocity;
+            if rb.kinetic_energy_per_mass() < SLEEP_ENERGY_THRESHOLD {
+                rb.rest_time += dt;
+            } else {
+                rb.rest_time = 0.0;
+            }
+            let root = islands.root(i);
+            island_rest_time[root] = island_rest_time[root].min(rb.rest_time);
+        }
+        for (i, rb) in rigid_bodies.values_mut().enumerate() {
+            if rb.sleeping || rb.inv_mass() == 0.0 {
+                continue;
+            }
+            if island_rest_time[islands.root(i)] >= TIME_TO_SLEEP {
+                rb.sleep();
+            }
+        }
+    }
+
+    // Pushes b out of a along dir until they only overlap by the slop, and
+    // stops them moving further into each other. Lighter bodies get moved
+    // further, static and kinematic ones stay put.
+    fn push_apart(&mut self, a: usize, b: usize, dir: Vec3, depth: f32) {
+        let rigid_bodies = &mut self.rigid_bodies;
+        // Leave resting bodies slightly overlapped so their contacts keep
+        // getting detected
+        let dist = depth - PENETRATION_SLOP;
+        if dist <= 0.0 {
+            return;
+        }
+        let (a_inv_mass, b_inv_mass) = (rigid_bodies[a].inv_mass(), rigid_bodies[b].inv_mass());
+        let total_inv_mass = a_inv_mass + b_inv_mass;
+        if total_inv_mass == 0.0 {
+            return;
+        }
+        let a_move_dist = -dist * (a_inv_mass / total_inv_mass);
+        let b_move_dist = dist * (b_inv_mass / total_inv_mass);
+        rigid_bodies[a].apply_orient(&Orientation {
+            translation: a_move_dist * dir,
+            rotation: Mat4::IDENTITY,
+        });
+        rigid_bodies[b].apply_orient(&Orientation {
+            translation: b_move_dist * dir,
+            rotation: Mat4::IDENTITY,
+        });
+        // Drop whatever speed they still had into each other, or it keeps
+        // building up whenever the contact goes missing
+        let approach =
+            (rigid_bodies[b].kinematics.velocity - rigid_bodies[a].kinematics.velocity).dot(dir);
+        if approach < 0.0 {
+            let impulse = -approach / total_inv_mass;
+            rigid_bodies[a].kinematics.velocity -= dir * impulse * a_inv_mass;
+            rigid_bodies[b].kinematics.velocity += dir * impulse * b_inv_mass;
+        }
+    }
+
+    // Pushes touching pairs apart by the deepest contact of their manifolds
+    fn push_apart_touches(&mut self, touches: &[Touch]) {
+        let mut deepest: IndexMap<(usize, usize), (Vec3, f32)> = IndexMap::new();
+        for (i, j, manifold) in touches {
+            let depth = manifold.points.iter().map(|p| p.depth).fold(0.0, f32::max);
+            let entry = deepest.entry((*i, *j)).or_insert((manifold.normal, depth));
+            if depth > entry.1 {
+                *entry = (manifold.normal, depth);
+            }
+        }
+        for ((a, b), (dir, depth)) in deepest {
+            self.push_apart(a, b, dir, depth);
+        }
+    }
+
+    // Pushes apart the pairs among pairs that sank into each other while
+    // moving this step. Returns the pairs left resting on each other, the
+    // next step should find contacts for all of them.
+    fn resolve_penetrations(
+        &mut self,
+        pairs: &[(usize, usize)],
+        moved: &[bool],
+    ) -> Vec<(Entity, Entity)> {
+        let mut resting = vec![];
+        for &(i, j) in pairs.iter().filter(|(i, j)| moved[*i] || moved[*j]) {
+            let rigid_bodies = &self.rigid_bodies;
+            let cache = self
+                .pair_caches
+                .entry(pair_key(rigid_bodies, i, j))
+                .or_default();
+            // Compounds get pushed out of their deepest part
+            let Some(touch_info) = rigid_bodies[i]
+                .orient_shape
+                .part_pairs(&rigid_bodies[j].orient_shape)
+                .into_iter()
+                .filter_map(|(parts, a, b)| {
+                    let default_dir = b.center_hint() - a.center_hint();
+                    let dir = cache.gjk_dir(parts, default_dir.normalize_or(Vec3::X));
+                    IntersectionInfo::new_with_gjk_from(&a, &b, dir)
+                        .inspect_err(|e| log::warn!("penetration test skipped: {e}"))
+                        .ok()
+                        .flatten()
+                })
+                .max_by(|x, y| x.dist.total_cmp(&y.dist))
+            else {
+                continue;
+            };
+            if touch_info.dist >= PENETRATION_SLOP {
+                resting.push(pair_key(rigid_bodies, i, j));
+            }
+            self.push_apart(i, j, touch_info.dir, touch_info.dist);
+        }
+        resting
+    }
+
+    // Bodies the last step left resting on each other but without contacts
+    // now would skip friction and restitution, the narrow phase should never
+    // lose them
+    fn check_resting_contacts(&self, tested: &[(usize, usize)], touches: &[Touch]) {
+        for (a, b) in &self.resting {
+            let (Some(i), Some(j)) = (
+                self.rigid_bodies.get_index_of(a),
+                self.rigid_bodies.get_index_of(b),
+            ) else {
+                continue;
+            };
+            let pair = (i.min(j), i.max(j));
+            if tested.binary_search(&pair).is_ok()
+                && !touches.iter().any(|(i, j, _)| (*i, *j) == pair)
+            {
+                log::warn!("{a:?} and {b:?} are resting on each other without contacts");
+            }
+        }
+    }
+
+    // Narrow phase over the pairs that can push each other, testing each pair
+    // once. Sleeping bodies touching an active one wake up and get their own
+    // pairs tested too, until nothing new wakes so whole resting stacks wake
+    // together. Returns the tested pairs and the manifolds of every touching
+    // part pair, both sorted by index so the solver sees them in the same
+    // order every run.
+    fn find_touches(&mut self, overlaps: &[(usize, usize)]) -> (Vec<(usize, usize)>, Vec<Touch>) {
+        let mut tested = HashSet::new();
+        let mut touches = vec![];
+        loop {
+            self.wake_joined();
+            let mut pairs = self.candidate_pairs(overlaps);
+            pairs.retain(|pair| !tested.contains(pair));
+            if pairs.is_empty() {
+                break;
+            }
+            let found = self.touch_pairs(&pairs);
+            for (i, j, _) in &found {
+                let (rb_a, rb_b) = (&self.rigid_bodies[*i], &self.rigid_bodies[*j]);
+                if rb_a.sleeping && rb_b.is_active() {
+                    self.rigid_bodies[*i].wake_up();
+                } else if rb_b.sleeping && rb_a.is_active() {
+                    self.rigid_bodies[*j].wake_up();
                 }
+            }
+            tested.extend(pairs);
+            touches.extend(found);
+        }
+        let mut tested: Vec<_> = tested.into_iter().collect();
+        tested.sort_unstable();
+        // Stable, so the parts of a pair stay in order
+        touches.sort_by_key(|(i, j, _)| (*i, *j));
+        (tested, touches)
+    }
 
-                let rel_acc = rigid_bodies[i].kinematics.acceleration
-                    - rigid_bodies[*j].kinematics.acceleration;
-                let acc_component = rel_acc.dot(-touch_info.dir);
-                if acc_component < 0.0 {
-                    let blocked_acc = rel_acc - acc_component * -touch_info.dir;
-                    rigid_bodies[i].kinematics.acceleration =
-                        blocked_acc + rigid_bodies[*j].kinematics.acceleration;
+    // Manifolds of every touching part pair among pairs, starting each GJK
+    // search where the pair's last one ended
+    fn touch_pairs(&mut self, pairs: &[(usize, usize)]) -> Vec<Touch> {
+        let rigid_bodies = &self.rigid_bodies;
+        let mut touches = vec![];
+        for &(i, j) in pairs {
+            let cache = self
+                .pair_caches
+                .entry(pair_key(rigid_bodies, i, j))
+                .or_default();
+            let (shape_a, shape_b) = (&rigid_bodies[i].orient_shape, &rigid_bodies[j].orient_shape);
+            let part_pairs = shape_a.part_pairs(shape_b);
+            for (parts, part_a, part_b) in &part_pairs {
+                let default_dir = part_b.center_hint() - part_a.center_hint();
+                let dir = cache.gjk_dir(*parts, default_dir.normalize_or(Vec3::X));
+                match ContactManifold::new_with_gjk_from(part_a, part_b, dir) {
+                    Ok(Some(manifold)) => touches.push((i, j, manifold)),
+                    Ok(None) => {}
+                    Err(e) => log::warn!("contact test skipped: {e}"),
                 }
             }
+            // Parts that drifted apart don't need their directions anymore
+            let near: HashSet<_> = part_pairs.iter().map(|(parts, _, _)| *parts).collect();
+            cache.gjk_dirs.retain(|parts, _| near.contains(parts));
         }
-        for rb in rigid_bodies.iter_mut() {
-            rb.1.fwd_ms();
+        touches
+    }
+
+    // Forgets pairs whose bounds stopped overlapping. Pairs of resting bodies
+    // aren't tested, so they keep theirs for when they wake up.
+    fn prune_pair_caches(&mut self, tested: &[(usize, usize)]) {
+        let rigid_bodies = &self.rigid_bodies;
+        let tested: HashSet<_> = tested
+            .iter()
+            .map(|&(i, j)| pair_key(rigid_bodies, i, j))
+            .collect();
+        self.pair_caches.retain(|key, _| {
+            if tested.contains(key) {
+                return true;
+            }
+            let (Some(rb_a), Some(rb_b)) = (rigid_bodies.get(&key.0), rigid_bodies.get(&key.1))
+            else {
+                return false;
+            };
+            !rb_a.is_active() && !rb_b.is_active()
+        });
+    }
+
+    pub fn step(&mut self) {
+        let dt = self.config.dt;
+        for rb in self.rigid_bodies.values_mut() {
+            rb.prev_orient = rb.orient.clone();
+        }
+        // Everything this step tests comes out of one broad phase pass
+        let overlaps = self
+            .broad_phase
+            .update(&self.rigid_bodies, dt, self.config.gravity);
+        self.wake_pushed();
+        // The narrow phase runs once per pair, its manifolds push apart
+        // existing penetrations and then go to the solver
+        let (pairs, touches) = self.find_touches(&overlaps);
+        self.check_resting_contacts(&pairs, &touches);
+        self.push_apart_touches(&touches);
+        for rb in self.rigid_bodies.values_mut().filter(|rb| !rb.sleeping) {
+            rb.integrate_velocity(dt, self.config.gravity);
+        }
+        self.prune_pair_caches(&pairs);
+        self.stats = StepStats {
+            awake_bodies: self
+                .rigid_bodies
+                .values()
+                .filter(|rb| rb.is_active())
+                .count(),
+            pair_tests: pairs.len(),
+            touches: touches.len(),
+            contact_points: touches.iter().map(|(_, _, m)| m.points.len()).sum(),
+        };
+        // Resolve contact velocities
+        let mut solver = ContactSolver::new(
+            &self.rigid_bodies,
+            &touches,
+            &self.joints,
+            &self.pair_caches,
+            dt,
+        );
+        solver.solve(&mut self.rigid_bodies);
+        solver.store_impulses(&self.rigid_bodies, &pairs, &mut self.pair_caches);
+        let moved: Vec<_> = self
+            .rigid_bodies
+            .values()
+            .map(|rb| {
+                rb.kinematics.velocity != Vec3::ZERO || rb.kinematics.angular_velocity != Vec3::ZERO
+            })
+            .collect();
+        for i in 0..self.rigid_bodies.len() {
+            let rb = &self.rigid_bodies[i];
+            let motion = rb.kinematics.velocity * dt;
+            let impact = if rb.ccd && !rb.is_sensor && motion != Vec3::ZERO {
+                self.time_of_impact(i, motion)
+            } else {
+                None
+            };
+            match impact {
+                Some(hit) => {
+                    self.rigid_bodies[i].integrate_position_to_impact(dt, hit.fraction, hit.normal)
+                }
+                None => self.rigid_bodies[i].integrate_position(dt),
+            }
         }
-        // resolve existing penetrations
-        self.resolve_penetrations(rigid_bodies);
+        // Only pairs that moved can have sunk into each other since
+        self.resting = self.resolve_penetrations(&pairs, &moved);
+        self.update_sleep(&touches);
+        self.update_events(&touches, &overlaps);
+        let rigid_bodies = &self.rigid_bodies;
+        self.contacts = touches
+            .into_iter()
+            .map(|(i, j, manifold)| {
+                let (a, b) = pair_key(rigid_bodies, i, j);
+                (a, b, manifold)
+            })
+            .collect();
     }
 }
@@ -0,0 +1,175 @@
+use std::f32::consts::PI;
+
+use glam::{Mat3, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    collision_shape::{CollisionShape, Mesh},
+    utils::{inertia_from_covariance, outer_product},
+};
+
+// Density bodies get when the level gives neither a mass nor a density
+pub const DEFAULT_DENSITY: f32 = 1.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MassProperties {
+    pub volume: f32,
+    pub mass: f32,
+    // Center of mass in the shape's own frame
+    pub center: Vec3,
+    // Inertia tensor about the center of mass
+    pub inertia: Mat3,
+}
+
+impl MassProperties {
+    // Same distribution of mass, scaled to a different total
+    pub fn with_mass(&self, mass: f32) -> Self {
+        let scale = if self.mass > f32::EPSILON {
+            mass / self.mass
+        } else {
+            0.0
+        };
+        Self {
+            volume: self.volume,
+            mass,
+            center: self.center,
+            inertia: self.inertia * scale,
+        }
+    }
+
+    // Adds up parts given in the same frame, shifting their inertia onto the
+    // combined center with the parallel axis theorem
+    fn combine(parts: &[Self], fallback_center: Vec3) -> Self {
+        let volume = parts.iter().map(|p| p.volume).sum();
+        let mass: f32 = parts.iter().map(|p| p.mass).sum();
+        let center = if mass > f32::EPSILON {
+            parts.iter().map(|p| p.center * p.mass).sum::<Vec3>() / mass
+        } else {
+            fallback_center
+        };
+        let inertia = parts
+            .iter()
+            .map(|p| {
+                let d = p.center - center;
+                p.inertia
+                    + (Mat3::from_diagonal(Vec3::splat(d.length_squared())) - outer_product(d, d))
+                        * p.mass
+            })
+            .sum();
+        Self {
+            volume,
+            mass,
+            center,
+            inertia,
+        }
+    }
+}
+
+fn mesh_mass_properties(mesh: &Mesh, density: f32) -> MassProperties {
+    // Integrate over tetrahedrons fanned out from the mesh center. Flat meshes
+    // have no volume, so they're weighed as thin shells of unit thickness
+    // instead: density is taken per unit of surface area, both sides of a
+    // flat shape counting, and volume stays 0. That keeps a sensible spread of
+    // mass for with_mass to scale.
+    let mut vol = 0.0;
+    let mut vol_moment = Vec3::ZERO;
+    let mut vol_cov = Mat3::ZERO;
+    let mut area = 0.0;
+    let mut area_moment = Vec3::ZERO;
+    let mut area_cov = Mat3::ZERO;
+    for face in &mesh.faces {
+        let p0 = mesh.points[face[0] as usize] - mesh.center;
+        for w in face[1..].windows(2) {
+            let p1 = mesh.points[w[0] as usize] - mesh.center;
+            let p2 = mesh.points[w[1] as usize] - mesh.center;
+            let sum = p0 + p1 + p2;
+            let pts_cov = outer_product(p0, p0)
+                + outer_product(p1, p1)
+                + outer_product(p2, p2)
+                + outer_product(sum, sum);
+            let tet_vol = p0.dot(p1.cross(p2)) / 6.0;
+            vol += tet_vol;
+            vol_moment += sum * (tet_vol / 4.0);
+            vol_cov += pts_cov * (tet_vol / 20.0);
+            let tri_area = (p1 - p0).cross(p2 - p0).length() / 2.0;
+            area += tri_area;
+            area_moment += sum * (tri_area / 3.0);
+            area_cov += pts_cov * (tri_area / 12.0);
+        }
+    }
+    // Faces wound the other way give negative volumes throughout
+    let (measure, moment, cov) = if vol.abs() > f32::EPSILON {
+        (vol, vol_moment, vol_cov)
+    } else if area > f32::EPSILON {
+        (area, area_moment, area_cov)
+    } else {
+        return MassProperties {
+            volume: 0.0,
+            mass: 0.0,
+            center: mesh.center,
+            inertia: Mat3::ZERO,
+        };
+    };
+    let mass = density * measure.abs();
+    let offset = moment / measure;
+    let cov = cov * (mass / measure) - outer_product(offset, offset) * mass;
+    MassProperties {
+        volume: vol.abs(),
+        mass,
+        center: mesh.center + offset,
+        inertia: inertia_from_covariance(cov),
+    }
+}
+
+impl CollisionShape {
+    pub fn mass_properties(&self, density: f32) -> MassProperties {
+        match self {
+            Self::Sphere(sphere) => {
+                let volume = self.volume();
+                let mass = density * volume;
+                MassProperties {
+                    volume,
+                    mass,
+                    center: sphere.center,
+                    inertia: Mat3::from_diagonal(Vec3::splat(
+                        0.4 * mass * sphere.radius * sphere.radius,
+                    )),
+                }
+            }
+            Self::Capsule(capsule) => {
+                let r = capsule.radius;
+                let axis = capsule.b - capsule.a;
+                let h = axis.length();
+                let cyl_mass = density * PI * r * r * h;
+                let caps_mass = density * 4.0 / 3.0 * PI * r * r * r;
+                let i_axis = cyl_mass * r * r / 2.0 + caps_mass * 0.4 * r * r;
+                let i_perp = cyl_mass * (h * h / 12.0 + r * r / 4.0)
+                    + caps_mass * (0.4 * r * r + h * h / 4.0 + 3.0 * h * r / 8.0);
+                let u = axis.normalize_or_zero();
+                MassProperties {
+                    volume: self.volume(),
+                    mass: cyl_mass + caps_mass,
+                    center: (capsule.a + capsule.b) / 2.0,
+                    inertia: Mat3::from_diagonal(Vec3::splat(i_perp))
+                        + outer_product(u, u) * (i_axis - i_perp),
+                }
+            }
+            Self::Mesh(mesh) => mesh_mass_properties(mesh, density),
+            Self::Compound(compound) => {
+                let parts: Vec<_> = compound
+                    .children
+                    .iter()
+                    .map(|c| c.mass_properties(density))
+                    .collect();
+                MassProperties::combine(&parts, compound.center)
+            }
+            // Open surface, only used for static bodies
+            Self::TriangleMesh(tri_mesh) => MassProperties {
+                volume: 0.0,
+                mass: 0.0,
+                center: tri_mesh.center(),
+                inertia: Mat3::ZERO,
+            },
+        }
+    }
+}
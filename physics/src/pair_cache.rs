@@ -0,0 +1,40 @@
+use glam::Vec3;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+// Cached contacts further apart than this are treated as new ones
+const WARM_START_DISTANCE: f32 = 0.05;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct CachedContact {
+    // Contact point in body a's local frame
+    pub local_point: Vec3,
+    pub normal_impulse: f32,
+    pub tangent_impulse: Vec3,
+}
+
+// What the last step learned about a pair of bodies with overlapping bounds,
+// so the next one doesn't start from scratch
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct PairCache {
+    // Direction GJK finished along for each part pair, keyed by the parts'
+    // indices. The next search starts from it.
+    pub gjk_dirs: IndexMap<(usize, usize), Vec3>,
+    // Impulses the solver ended up with, used to warm start it
+    pub contacts: Vec<CachedContact>,
+}
+
+impl PairCache {
+    pub fn gjk_dir(&mut self, parts: (usize, usize), default: Vec3) -> &mut Vec3 {
+        self.gjk_dirs.entry(parts).or_insert(default)
+    }
+
+    pub fn find_contact(&self, local_point: Vec3) -> Option<&CachedContact> {
+        self.contacts
+            .iter()
+            .map(|c| (c, c.local_point.distance_squared(local_point)))
+            .filter(|(_, dist_sq)| *dist_sq <= WARM_START_DISTANCE * WARM_START_DISTANCE)
+            .min_by(|x, y| x.1.total_cmp(&y.1))
+            .map(|(c, _)| c)
+    }
+}
@@ -0,0 +1,198 @@
+use common::Entity;
+use glam::Vec3;
+
+use crate::{
+    CollisionFilter, Orientation, PhysicsManager, RigidBody,
+    collision_shape::CollisionShape,
+    simplex::{SupportPoint, closest_point_to},
+};
+
+const CAST_MAX_ITERATIONS: usize = 64;
+const CAST_EPSILON: f32 = 0.0001;
+
+#[derive(Debug, Clone)]
+pub struct RayHit {
+    pub entity: Entity,
+    pub point: Vec3,
+    pub normal: Vec3,
+    // Fraction of the cast distance travelled before the hit
+    pub fraction: f32,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct CastHit {
+    pub fraction: f32,
+    pub point: Vec3,
+    pub normal: Vec3,
+}
+
+// GJK ray cast (van den Bergen) of shape a moving by translation against b.
+// Both shapes must already be in world space.
+pub(crate) fn cast_shape(
+    a: &CollisionShape,
+    b: &CollisionShape,
+    translation: Vec3,
+) -> Option<CastHit> {
+    let support = |dir: Vec3| {
+        let on_a = a.farthest_point_along(-dir);
+        let on_b = b.farthest_point_along(dir);
+        SupportPoint {
+            p: on_b - on_a,
+            on_a,
+            on_b,
+        }
+    };
+    let mut lambda = 0.0;
+    let mut x = Vec3::ZERO;
+    let mut normal = Vec3::ZERO;
+    let mut simplex: Vec<SupportPoint> = vec![];
+    let mut closest = support(translation);
+    let mut v = x - closest.p;
+    let mut witness = closest.on_b;
+    for _ in 0..CAST_MAX_ITERATIONS {
+        if v.length_squared() <= CAST_EPSILON * CAST_EPSILON {
+            break;
+        }
+        closest = support(v);
+        let w = x - closest.p;
+        if v.dot(w) > 0.0 {
+            let v_dot_r = v.dot(translation);
+            if v_dot_r >= 0.0 {
+                return None;
+            }
+            lambda -= v.dot(w) / v_dot_r;
+            if lambda > 1.0 {
+                return None;
+            }
+            x = translation * lambda;
+            normal = v;
+        }
+        simplex.push(closest);
+        let closest_pt = closest_point_to(&mut simplex, x);
+        v = x - closest_pt.point;
+        witness = closest_pt.on_b;
+        if simplex.len() == 4 {
+            // The target is inside the simplex
+            break;
+        }
+    }
+    let normal = if normal == Vec3::ZERO {
+        // Already touching at the start of the cast
+        -translation.normalize_or_zero()
+    } else {
+        normal.normalize()
+    };
+    Some(CastHit {
+        fraction: lambda,
+        point: witness,
+        normal,
+    })
+}
+
+// Earliest hit of a world space shape moving by translation against a body
+fn cast_against_body(shape: &CollisionShape, rb: &RigidBody, translation: Vec3) -> Option<CastHit> {
+    shape
+        .convex_parts()
+        .into_iter()
+        .flat_map(|a| {
+            rb.orient_shape
+                .parts_near(&a.aabb().swept(translation))
+                .into_iter()
+                .filter_map(move |(_, b)| cast_shape(&a, &b, translation))
+        })
+        .min_by(|x, y| x.fraction.total_cmp(&y.fraction))
+}
+
+impl PhysicsManager {
+    // Casts shape from orient along dir for max_dist and reports every body hit
+    fn cast_all(
+        &self,
+        shape: &CollisionShape,
+        dir: Vec3,
+        max_dist: f32,
+        filter: &CollisionFilter,
+    ) -> Vec<RayHit> {
+        let translation = dir.normalize_or_zero() * max_dist;
+        let swept = shape.aabb().swept(translation);
+        let mut hits = vec![];
+        for (entity, rb) in &self.rigid_bodies {
+            // Sensors have no surface to hit
+            if rb.is_sensor || !filter.can_interact(&rb.filter) || !swept.overlaps(&rb.aabb) {
+                continue;
+            }
+            let Some(hit) = cast_against_body(shape, rb, translation) else {
+                continue;
+            };
+            hits.push(RayHit {
+                entity: *entity,
+                point: hit.point,
+                normal: hit.normal,
+                fraction: hit.fraction,
+            });
+        }
+        hits.sort_by(|a, b| a.fraction.total_cmp(&b.fraction));
+        hits
+    }
+
+    pub fn raycast(
+        &self,
+        origin: Vec3,
+        dir: Vec3,
+        max_dist: f32,
+        filter: &CollisionFilter,
+    ) -> Option<RayHit> {
+        self.raycast_all(origin, dir, max_dist, filter)
+            .into_iter()
+            .next()
+    }
+
+    // All hits along the ray, nearest first
+    pub fn raycast_all(
+        &self,
+        origin: Vec3,
+        dir: Vec3,
+        max_dist: f32,
+        filter: &CollisionFilter,
+    ) -> Vec<RayHit> {
+        let point = CollisionShape::new_sphere(origin, 0.0);
+        self.cast_all(&point, dir, max_dist, filter)
+    }
+
+    pub fn shape_cast(
+        &self,
+        shape: &CollisionShape,
+        orient: &Orientation,
+        dir: Vec3,
+        max_dist: f32,
+        filter: &CollisionFilter,
+    ) -> Option<RayHit> {
+        let shape = shape.with_orientation(orient);
+        self.cast_all(&shape, dir, max_dist, filter)
+            .into_iter()
+            .next()
+    }
+
+    // Where body i first hits another one over this step's motion: the
+    // fraction of the motion it can make and the normal of what it hits.
+    // Bodies already touching at the start are left to the contact solver.
+    // Only the translation is swept, the body keeps its current rotation for
+    // the whole cast, so a fast spinning body can still clip thin shapes.
+    pub(crate) fn time_of_impact(&self, i: usize, translation: Vec3) -> Option<CastHit> {
+        let moving = &self.rigid_bodies[i];
+        let swept = moving.aabb.swept(translation);
+        let joined = self.joined_pairs();
+        self.rigid_bodies
+            .values()
+            .enumerate()
+            .filter(|(j, rb)| {
+                *j != i
+                    && !rb.is_sensor
+                    && !joined.contains(&(i.min(*j), i.max(*j)))
+                    && moving.filter.can_interact(&rb.filter)
+                    && swept.overlaps(&rb.aabb)
+            })
+            .filter_map(|(_, rb)| cast_against_body(&moving.orient_shape, rb, translation))
+            .filter(|hit| hit.fraction > 0.0)
+            .min_by(|x, y| x.fraction.total_cmp(&y.fraction))
+    }
+}
@@ -0,0 +1,151 @@
+use glam::Vec3;
+
+const INSIDE_EPSILON: f32 = 0.00001;
+
+// Minkowski difference point along with the shape points that produced it
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SupportPoint {
+    pub p: Vec3,
+    pub on_a: Vec3,
+    pub on_b: Vec3,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ClosestPoint {
+    pub point: Vec3,
+    pub on_a: Vec3,
+    pub on_b: Vec3,
+}
+
+fn segment_weights(a: Vec3, b: Vec3) -> [f32; 2] {
+    let ab = b - a;
+    let len_sq = ab.length_squared();
+    if len_sq <= f32::EPSILON {
+        return [1.0, 0.0];
+    }
+    let t = (-a.dot(ab) / len_sq).clamp(0.0, 1.0);
+    [1.0 - t, t]
+}
+
+// Barycentric weights of the point on triangle abc closest to the origin
+fn triangle_weights(a: Vec3, b: Vec3, c: Vec3) -> [f32; 3] {
+    let ab = b - a;
+    let ac = c - a;
+    let d1 = ab.dot(-a);
+    let d2 = ac.dot(-a);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return [1.0, 0.0, 0.0];
+    }
+    let d3 = ab.dot(-b);
+    let d4 = ac.dot(-b);
+    if d3 >= 0.0 && d4 <= d3 {
+        return [0.0, 1.0, 0.0];
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        let v = d1 / (d1 - d3);
+        return [1.0 - v, v, 0.0];
+    }
+    let d5 = ab.dot(-c);
+    let d6 = ac.dot(-c);
+    if d6 >= 0.0 && d5 <= d6 {
+        return [0.0, 0.0, 1.0];
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        let w = d2 / (d2 - d6);
+        return [1.0 - w, 0.0, w];
+    }
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+        let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+        return [0.0, 1.0 - w, w];
+    }
+    let sum = va + vb + vc;
+    if sum.abs() <= f32::EPSILON {
+        // Degenerate triangle, fall back to its longest edge
+        let bc = c - b;
+        let longest = ab
+            .length_squared()
+            .max(ac.length_squared())
+            .max(bc.length_squared());
+        return if longest == ab.length_squared() {
+            let [s, t] = segment_weights(a, b);
+            [s, t, 0.0]
+        } else if longest == ac.length_squared() {
+            let [s, t] = segment_weights(a, c);
+            [s, 0.0, t]
+        } else {
+            let [s, t] = segment_weights(b, c);
+            [0.0, s, t]
+        };
+    }
+    let v = vb / sum;
+    let w = vc / sum;
+    [1.0 - v - w, v, w]
+}
+
+// Weights of the closest point and whether the origin is inside
+fn tetrahedron_weights(pts: [Vec3; 4]) -> ([f32; 4], bool) {
+    let [a, b, c, d] = pts;
+    let vol = (b - a).dot((c - a).cross(d - a));
+    if vol.abs() > f32::EPSILON {
+        // Signed volumes with the origin swapped in for each vertex
+        let weights = [
+            b.dot(c.cross(d)) / vol,
+            -a.dot(c.cross(d)) / vol,
+            a.dot(b.cross(d)) / vol,
+            -a.dot(b.cross(c)) / vol,
+        ];
+        // Origin lying on a face still counts as enclosed
+        if weights.iter().all(|&w| w >= -INSIDE_EPSILON) {
+            return (weights.map(|w| w.max(0.0)), true);
+        }
+    }
+    let faces = [[0, 1, 2], [0, 1, 3], [0, 2, 3], [1, 2, 3]];
+    let mut best = [0.0; 4];
+    let mut best_dist = f32::INFINITY;
+    for face in faces {
+        let w = triangle_weights(pts[face[0]], pts[face[1]], pts[face[2]]);
+        let point = pts[face[0]] * w[0] + pts[face[1]] * w[1] + pts[face[2]] * w[2];
+        let dist = point.length_squared();
+        if dist < best_dist {
+            best_dist = dist;
+            best = [0.0; 4];
+            for (i, &idx) in face.iter().enumerate() {
+                best[idx] = w[i];
+            }
+        }
+    }
+    (best, false)
+}
+
+// Finds the point of the simplex closest to target and drops the vertices
+// that don't contribute to it.
+pub(crate) fn closest_point_to(simplex: &mut Vec<SupportPoint>, target: Vec3) -> ClosestPoint {
+    let rel: Vec<Vec3> = simplex.iter().map(|s| s.p - target).collect();
+    let (weights, enclosed): (Vec<f32>, bool) = match rel.len() {
+        1 => (vec![1.0], false),
+        2 => (segment_weights(rel[0], rel[1]).to_vec(), false),
+        3 => (triangle_weights(rel[0], rel[1], rel[2]).to_vec(), false),
+        _ => {
+            let (weights, enclosed) = tetrahedron_weights([rel[0], rel[1], rel[2], rel[3]]);
+            (weights.to_vec(), enclosed)
+        }
+    };
+    let mut out = ClosestPoint {
+        point: Vec3::ZERO,
+        on_a: Vec3::ZERO,
+        on_b: Vec3::ZERO,
+    };
+    for (s, &w) in simplex.iter().zip(&weights) {
+        out.point += s.p * w;
+        out.on_a += s.on_a * w;
+        out.on_b += s.on_b * w;
+    }
+    if !enclosed {
+        let mut weights = weights.into_iter();
+        simplex.retain(|_| weights.next().unwrap_or(0.0) > 0.0);
+    }
+    out
+}
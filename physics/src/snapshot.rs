@@ -0,0 +1,144 @@
+use std::sync::Arc;
+
+use common::Entity;
+use glam::{Mat3, Vec3};
+use indexmap::{IndexMap, IndexSet};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    BodyType, CollisionFilter, Kinematics, Material, Orientation, PhysicsConfig, PhysicsManager,
+    RigidBody, broad_phase::SweepAndPrune, collision_shape::CollisionShape, event::PhysicsEvent,
+    handle::BodySlots, joint::Joint, mass::MassProperties, pair_cache::PairCache,
+};
+
+// Everything a rigid body needs to carry on exactly where it left off. The
+// oriented shape and bounds are caches and get rebuilt from these.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct RigidBodyState {
+    body_type: BodyType,
+    shape: Arc<CollisionShape>,
+    orient: Orientation,
+    prev_orient: Orientation,
+    kinematics: Kinematics,
+    material: Material,
+    can_rotate: bool,
+    gravity_scale: f32,
+    filter: CollisionFilter,
+    ccd: bool,
+    is_sensor: bool,
+    mass_props: MassProperties,
+    inv_inertia: Mat3,
+    force: Vec3,
+    torque: Vec3,
+    impulse: Vec3,
+    angular_impulse: Vec3,
+    sleeping: bool,
+    rest_time: f32,
+}
+
+impl From<RigidBody> for RigidBodyState {
+    fn from(rb: RigidBody) -> Self {
+        Self {
+            body_type: rb.body_type,
+            shape: rb.shape,
+            orient: rb.orient,
+            prev_orient: rb.prev_orient,
+            kinematics: rb.kinematics,
+            material: rb.material,
+            can_rotate: rb.can_rotate,
+            gravity_scale: rb.gravity_scale,
+            filter: rb.filter,
+            ccd: rb.ccd,
+            is_sensor: rb.is_sensor,
+            mass_props: rb.mass_props,
+            inv_inertia: rb.inv_inertia,
+            force: rb.force,
+            torque: rb.torque,
+            impulse: rb.impulse,
+            angular_impulse: rb.angular_impulse,
+            sleeping: rb.sleeping,
+            rest_time: rb.rest_time,
+        }
+    }
+}
+
+impl From<RigidBodyState> for RigidBody {
+    fn from(state: RigidBodyState) -> Self {
+        let orient_shape = state.shape.with_orientation(&state.orient);
+        Self {
+            body_type: state.body_type,
+            shape: state.shape,
+            orient: state.orient,
+            aabb: orient_shape.aabb(),
+            orient_shape,
+            prev_orient: state.prev_orient,
+            kinematics: state.kinematics,
+            material: state.material,
+            can_rotate: state.can_rotate,
+            gravity_scale: state.gravity_scale,
+            filter: state.filter,
+            ccd: state.ccd,
+            is_sensor: state.is_sensor,
+            mass_props: state.mass_props,
+            inv_inertia: state.inv_inertia,
+            force: state.force,
+            torque: state.torque,
+            impulse: state.impulse,
+            angular_impulse: state.angular_impulse,
+            sleeping: state.sleeping,
+            rest_time: state.rest_time,
+        }
+    }
+}
+
+// Whole simulation state at some point. Restoring it and stepping with the
+// same inputs gives bit identical results to carrying on from where it was
+// taken.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PhysicsSnapshot {
+    pub config: PhysicsConfig,
+    rigid_bodies: IndexMap<Entity, RigidBody>,
+    joints: Vec<Joint>,
+    slots: BodySlots,
+    accumulator: f32,
+    pair_caches: IndexMap<(Entity, Entity), PairCache>,
+    touching: IndexSet<(Entity, Entity)>,
+    sensed: IndexSet<(Entity, Entity)>,
+    events: Vec<PhysicsEvent>,
+}
+
+impl PhysicsManager {
+    pub fn snapshot(&self) -> PhysicsSnapshot {
+        PhysicsSnapshot {
+            config: self.config.clone(),
+            rigid_bodies: self.rigid_bodies.clone(),
+            joints: self.joints.clone(),
+            slots: self.slots.clone(),
+            accumulator: self.accumulator,
+            pair_caches: self.pair_caches.clone(),
+            touching: self.touching.clone(),
+            sensed: self.sensed.clone(),
+            events: self.events.clone(),
+        }
+    }
+
+    // Handles taken before the snapshot was made point to the same bodies
+    // again after restoring it
+    pub fn restore(&mut self, snapshot: PhysicsSnapshot) {
+        self.config = snapshot.config.validated();
+        self.rigid_bodies = snapshot.rigid_bodies;
+        self.joints = snapshot.joints;
+        self.slots = snapshot.slots;
+        self.accumulator = snapshot.accumulator;
+        self.pair_caches = snapshot.pair_caches;
+        self.touching = snapshot.touching;
+        self.sensed = snapshot.sensed;
+        self.events = snapshot.events;
+        // Only a cache of the sort order, pairs come out sorted either way
+        self.broad_phase = SweepAndPrune::new();
+        // Only drawn, the next step finds them again
+        self.contacts.clear();
+        // Only checked against, not carried into the next step
+        self.resting.clear();
+    }
+}
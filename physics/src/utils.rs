@@ -1,13 +1,17 @@
 use glam::Vec4Swizzles;
 
+use crate::error::CollisionError;
+
 pub fn point_vec4(p: glam::Vec3) -> glam::Vec4 {
     glam::Vec4::from((p, 1.0))
 }
 
+#[allow(dead_code)]
 pub fn dir_vec4(p: glam::Vec3) -> glam::Vec4 {
     glam::Vec4::from((p, 0.0))
 }
 
+#[allow(dead_code)]
 pub fn orient_plane(pl: glam::Vec4, tr: &glam::Mat4) -> glam::Vec4 {
     let new_n = tr * dir_vec4(pl.xyz());
     let new_p = tr * point_vec4(-pl.xyz() * pl.w);
@@ -18,26 +22,27 @@ pub fn new_plane(n: glam::Vec3, p: glam::Vec3) -> glam::Vec4 {
     glam::Vec4::from((n, -n.dot(p)))
 }
 
-pub fn get_triangle_normal(a: glam::Vec3, b: glam::Vec3, c: glam::Vec3) -> glam::Vec3 {
-    let e1 = b - a;
-    let e2 = c - b;
-    let n = e1.cross(e2).normalize();
-    if n.x.is_nan() {
-        println!("a: {}", a);
-        println!("b: {}", b);
-        println!("c: {}", c);
-        println!("e1: {}", e1);
-        println!("e2: {}", e2);
-        println!("e1 x e2: {}", e1.cross(e2));
-    }
-    n
+pub fn get_triangle_normal(
+    a: glam::Vec3,
+    b: glam::Vec3,
+    c: glam::Vec3,
+) -> Result<glam::Vec3, CollisionError> {
+    (b - a)
+        .cross(c - b)
+        .try_normalize()
+        .ok_or(CollisionError::DegenerateTriangle(a, b, c))
 }
 
-pub fn get_triangle_plane(a: glam::Vec3, b: glam::Vec3, c: glam::Vec3) -> glam::Vec4 {
-    let n = get_triangle_normal(a, b, c);
-    new_plane(n, a)
+pub fn get_triangle_plane(
+    a: glam::Vec3,
+    b: glam::Vec3,
+    c: glam::Vec3,
+) -> Result<glam::Vec4, CollisionError> {
+    let n = get_triangle_normal(a, b, c)?;
+    Ok(new_plane(n, a))
 }
 
+#[allow(dead_code)]
 pub fn points_on_side(pl: glam::Vec4, points: &[glam::Vec4]) -> Option<bool> {
     let mut side = None;
     for p in points {
@@ -48,10 +53,8 @@ pub fn points_on_side(pl: glam::Vec4, points: &[glam::Vec4]) -> Option<bool> {
                     if f_p_dist < 0.0 {
                         return None;
                     }
-                } else {
-                    if f_p_dist > 0.0 {
-                        return None;
-                    }
+                } else if f_p_dist > 0.0 {
+                    return None;
                 }
             }
             None => {
@@ -68,6 +71,7 @@ pub fn points_on_side(pl: glam::Vec4, points: &[glam::Vec4]) -> Option<bool> {
     side
 }
 
+#[allow(dead_code)]
 pub fn points_on_pos(pl: glam::Vec4, points: &[glam::Vec4]) -> bool {
     for p in points {
         if p.dot(pl) < 0.0 {
@@ -77,6 +81,7 @@ pub fn points_on_pos(pl: glam::Vec4, points: &[glam::Vec4]) -> bool {
     true
 }
 
+#[allow(dead_code)]
 pub fn points_min_dist(pl: glam::Vec4, points: &[glam::Vec4]) -> f32 {
     let mut min_dist = f32::INFINITY;
     for p in points {
@@ -88,6 +93,7 @@ pub fn points_min_dist(pl: glam::Vec4, points: &[glam::Vec4]) -> f32 {
     min_dist
 }
 
+#[allow(dead_code)]
 pub fn points_min_max_dist(pl: glam::Vec4, points: &[glam::Vec4]) -> (f32, f32) {
     let mut min_dist = f32::INFINITY;
     let mut max_dist = f32::NEG_INFINITY;
@@ -103,6 +109,7 @@ pub fn points_min_max_dist(pl: glam::Vec4, points: &[glam::Vec4]) -> (f32, f32)
     (min_dist, max_dist)
 }
 
+#[allow(dead_code)]
 pub fn project_on_line(a: glam::Vec3, b: glam::Vec3, p: glam::Vec3) -> (f32, glam::Vec3) {
     let ap = p - a;
     let ab = b - a;
@@ -110,6 +117,68 @@ pub fn project_on_line(a: glam::Vec3, b: glam::Vec3, p: glam::Vec3) -> (f32, gla
     (t, a + (ab * t))
 }
 
+#[allow(dead_code)]
 pub fn remove_component(v: &mut glam::Vec3, d: glam::Vec3) {
-    *v = *v - (d.dot(*v) * d);
+    *v -= d.dot(*v) * d;
+}
+
+pub fn outer_product(a: glam::Vec3, b: glam::Vec3) -> glam::Mat3 {
+    glam::Mat3::from_cols(a * b.x, a * b.y, a * b.z)
+}
+
+pub fn inertia_from_covariance(cov: glam::Mat3) -> glam::Mat3 {
+    let trace = cov.x_axis.x + cov.y_axis.y + cov.z_axis.z;
+    glam::Mat3::from_diagonal(glam::Vec3::splat(trace)) - cov
+}
+
+pub fn polygon_normal(points: &[glam::Vec3]) -> glam::Vec3 {
+    // Newell's method, robust for non-triangular and slightly non-planar faces
+    let mut n = glam::Vec3::ZERO;
+    for (i, p) in points.iter().enumerate() {
+        let q = points[(i + 1) % points.len()];
+        n += (*p - q).cross(*p + q) * 0.5;
+    }
+    n.normalize_or_zero()
+}
+
+pub fn closest_points_on_segments(
+    p1: glam::Vec3,
+    q1: glam::Vec3,
+    p2: glam::Vec3,
+    q2: glam::Vec3,
+) -> (glam::Vec3, glam::Vec3) {
+    let d1 = q1 - p1;
+    let d2 = q2 - p2;
+    let r = p1 - p2;
+    let a = d1.length_squared();
+    let e = d2.length_squared();
+    let f = d2.dot(r);
+    let (s, t) = if a <= f32::EPSILON && e <= f32::EPSILON {
+        (0.0, 0.0)
+    } else if a <= f32::EPSILON {
+        (0.0, (f / e).clamp(0.0, 1.0))
+    } else {
+        let c = d1.dot(r);
+        if e <= f32::EPSILON {
+            ((-c / a).clamp(0.0, 1.0), 0.0)
+        } else {
+            let b = d1.dot(d2);
+            let denom = a * e - b * b;
+            let mut s = if denom > f32::EPSILON {
+                ((b * f - c * e) / denom).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let mut t = (b * s + f) / e;
+            if t < 0.0 {
+                t = 0.0;
+                s = (-c / a).clamp(0.0, 1.0);
+            } else if t > 1.0 {
+                t = 1.0;
+                s = ((b - c) / a).clamp(0.0, 1.0);
+            }
+            (s, t)
+        }
+    };
+    (p1 + d1 * s, p2 + d2 * t)
 }
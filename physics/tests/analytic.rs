@@ -0,0 +1,575 @@
+// Overlaps with a depth and direction that can be worked out by hand
+
+mod support;
+
+use std::{
+    f32::consts::{FRAC_PI_2, FRAC_PI_4, SQRT_2},
+    sync::Arc,
+};
+
+use common::Entity;
+use glam::{Mat4, Quat, Vec3};
+use physics::{
+    BodyType, CollisionFilter, Kinematics, Orientation, PhysicsManager, RigidBody,
+    collision_shape::CollisionShape, contact_manifold::ContactManifold, distance::DistanceInfo,
+};
+use support::{assert_vec_near, cuboid, intersect, placed, separates};
+
+const DEPTH_TOLERANCE: f32 = 0.001;
+// Casts only approximate round shapes with a polytope, so their normals come
+// out a little off
+const ROUND_DIR_TOLERANCE: f32 = 0.02;
+
+#[test]
+fn sphere_sphere_overlap() {
+    let dir = Vec3::new(1.0, 2.0, 3.0).normalize();
+    let a = CollisionShape::new_sphere(Vec3::ZERO, 1.0);
+    let b = CollisionShape::new_sphere(dir * 1.2, 0.5);
+    let info = intersect(&a, &b).expect("spheres overlap");
+    assert!(
+        (info.dist - 0.3).abs() <= DEPTH_TOLERANCE,
+        "depth {}",
+        info.dist
+    );
+    assert_vec_near(info.dir, dir, DEPTH_TOLERANCE);
+}
+
+#[test]
+fn sphere_sphere_apart_or_touching() {
+    let a = CollisionShape::new_sphere(Vec3::ZERO, 1.0);
+    let apart = CollisionShape::new_sphere(Vec3::new(0.0, 2.0, 0.0), 0.5);
+    assert!(intersect(&a, &apart).is_none());
+    let touching = CollisionShape::new_sphere(Vec3::new(0.0, 1.5, 0.0), 0.5);
+    assert!(intersect(&a, &touching).is_none_or(|info| info.dist <= DEPTH_TOLERANCE));
+}
+
+#[test]
+fn sphere_on_box_face() {
+    let a = cuboid(Vec3::splat(0.5));
+    let b = CollisionShape::new_sphere(Vec3::new(0.2, 0.9, -0.1), 0.5);
+    let info = intersect(&a, &b).expect("sphere sinks into the box");
+    assert!(
+        (info.dist - 0.1).abs() <= DEPTH_TOLERANCE,
+        "depth {}",
+        info.dist
+    );
+    assert_vec_near(info.dir, Vec3::Y, DEPTH_TOLERANCE);
+}
+
+#[test]
+fn capsule_sphere_overlap_from_the_side() {
+    // Lying along x, the sphere sinks 0.1 into its side off the segment's
+    // middle, at a slant
+    let a = CollisionShape::new_capsule(Vec3::NEG_X, Vec3::X, 0.5);
+    let dir = Vec3::new(0.0, 3.0, 4.0).normalize();
+    let b = CollisionShape::new_sphere(Vec3::X * 0.4 + dir * 0.65, 0.25);
+    let info = intersect(&a, &b).expect("sphere sinks into the capsule");
+    assert!(
+        (info.dist - 0.1).abs() <= DEPTH_TOLERANCE,
+        "depth {}",
+        info.dist
+    );
+    assert_vec_near(info.dir, dir, DEPTH_TOLERANCE);
+    assert!(separates(&a, &b, &info));
+}
+
+#[test]
+fn box_box_face_overlap() {
+    let a = cuboid(Vec3::splat(0.5));
+    let b = placed(
+        &cuboid(Vec3::splat(0.5)),
+        Vec3::new(0.1, 0.8, 0.05),
+        Quat::IDENTITY,
+    );
+    let info = intersect(&a, &b).expect("boxes overlap");
+    assert!(
+        (info.dist - 0.2).abs() <= DEPTH_TOLERANCE,
+        "depth {}",
+        info.dist
+    );
+    assert_vec_near(info.dir, Vec3::Y, DEPTH_TOLERANCE);
+}
+
+#[test]
+fn box_box_shallowest_axis_wins() {
+    let a = cuboid(Vec3::new(2.0, 0.5, 1.0));
+    let b = placed(
+        &cuboid(Vec3::splat(0.5)),
+        Vec3::new(2.3, 0.2, 0.0),
+        Quat::IDENTITY,
+    );
+    let info = intersect(&a, &b).expect("boxes overlap");
+    assert!(
+        (info.dist - 0.2).abs() <= DEPTH_TOLERANCE,
+        "depth {}",
+        info.dist
+    );
+    assert_vec_near(info.dir, Vec3::X, DEPTH_TOLERANCE);
+}
+
+#[test]
+fn box_box_corner_into_face() {
+    // Turned 45 degrees around z, b's lowest edge dips 0.1 into a's top face
+    let a = cuboid(Vec3::splat(0.5));
+    let height = 0.5 + 0.5 * SQRT_2 - 0.1;
+    let b = placed(
+        &cuboid(Vec3::splat(0.5)),
+        Vec3::new(0.0, height, 0.0),
+        Quat::from_rotation_z(FRAC_PI_4),
+    );
+    let info = intersect(&a, &b).expect("boxes overlap");
+    assert!(
+        (info.dist - 0.1).abs() <= DEPTH_TOLERANCE,
+        "depth {}",
+        info.dist
+    );
+    assert_vec_near(info.dir, Vec3::Y, DEPTH_TOLERANCE);
+    assert!(separates(&a, &b, &info));
+}
+
+#[test]
+fn box_box_edge_into_edge() {
+    // Both boxes stand on an edge, crossed at right angles. Their edges meet
+    // head on, 0.1 deep.
+    let a = placed(
+        &cuboid(Vec3::splat(0.5)),
+        Vec3::ZERO,
+        Quat::from_rotation_z(FRAC_PI_4),
+    );
+    let b = placed(
+        &cuboid(Vec3::splat(0.5)),
+        Vec3::new(0.0, SQRT_2 - 0.1, 0.0),
+        Quat::from_rotation_x(FRAC_PI_4),
+    );
+    let info = intersect(&a, &b).expect("boxes overlap");
+    assert!(
+        (info.dist - 0.1).abs() <= DEPTH_TOLERANCE,
+        "depth {}",
+        info.dist
+    );
+    assert_vec_near(info.dir, Vec3::Y, DEPTH_TOLERANCE);
+}
+
+#[test]
+fn box_box_face_touching_is_not_an_overlap() {
+    let a = cuboid(Vec3::splat(0.5));
+    let b = placed(
+        &cuboid(Vec3::splat(0.5)),
+        Vec3::new(0.3, 1.0, 0.0),
+        Quat::IDENTITY,
+    );
+    assert!(intersect(&a, &b).is_none());
+}
+
+#[test]
+fn box_box_resting_manifold_has_four_corners() {
+    let a = cuboid(Vec3::new(2.0, 0.5, 2.0));
+    let b = placed(
+        &cuboid(Vec3::splat(0.5)),
+        Vec3::new(0.0, 0.99, 0.0),
+        Quat::IDENTITY,
+    );
+    let manifold = ContactManifold::new_with_gjk(&a, &b)
+        .expect("intersection test failed")
+        .expect("boxes overlap");
+    assert_vec_near(manifold.normal, Vec3::Y, DEPTH_TOLERANCE);
+    assert_eq!(manifold.points.len(), 4);
+    for contact in &manifold.points {
+        assert!((contact.depth - 0.01).abs() <= DEPTH_TOLERANCE);
+        assert!((contact.point.x.abs() - 0.5).abs() <= DEPTH_TOLERANCE);
+        assert!((contact.point.z.abs() - 0.5).abs() <= DEPTH_TOLERANCE);
+    }
+}
+
+#[test]
+fn triangle_mesh_follows_its_body() {
+    // Flat square turned upright, so it faces the z axis 3 away
+    let quad = CollisionShape::new_triangle_mesh(
+        &[
+            Vec3::new(1.0, 0.0, 1.0),
+            Vec3::new(-1.0, 0.0, 1.0),
+            Vec3::new(-1.0, 0.0, -1.0),
+            Vec3::new(1.0, 0.0, -1.0),
+        ],
+        &[0, 1, 2, 0, 2, 3],
+    )
+    .expect("building mesh failed");
+    let orient = Orientation {
+        translation: Vec3::new(0.0, 0.0, 3.0),
+        rotation: Mat4::from_rotation_x(FRAC_PI_2),
+    };
+    let filter = CollisionFilter::new(1, 0);
+    let mut physics = PhysicsManager::new();
+    let wall = RigidBody::new(
+        BodyType::Static,
+        Arc::new(quad),
+        orient,
+        Kinematics::new(),
+        false,
+        1.0,
+        filter,
+    );
+    physics
+        .add_body(Entity::new(0), wall)
+        .expect("adding body failed");
+    let hit = physics
+        .raycast(Vec3::new(0.5, 0.5, 0.0), Vec3::Z, 10.0, &filter)
+        .expect("ray hits the wall");
+    assert!((hit.fraction - 0.3).abs() <= DEPTH_TOLERANCE, "{hit:?}");
+    assert_vec_near(hit.point, Vec3::new(0.5, 0.5, 3.0), DEPTH_TOLERANCE);
+    // Where the square was before turning it, nothing is left
+    let missed = physics.raycast(Vec3::new(0.5, 1.0, 0.5), Vec3::NEG_Y, 2.0, &filter);
+    assert!(missed.is_none(), "{missed:?}");
+}
+
+#[test]
+fn sliver_triangle_distance_uses_its_longest_edge() {
+    // Small enough that GJK treats the triangle it builds as degenerate. It
+    // visits the corners in order, so a to b is the longest edge and c sits
+    // right next to a.
+    let sliver = CollisionShape::new_triangle_mesh(
+        &[
+            Vec3::new(-0.01, -0.0002, 0.01),
+            Vec3::new(0.03, -0.0002, 0.01),
+            Vec3::new(-0.009, 0.0005, 0.01),
+        ],
+        &[0, 1, 2],
+    )
+    .expect("building mesh failed");
+    let point = CollisionShape::new_sphere(Vec3::ZERO, 0.0);
+    let info = DistanceInfo::new_with_gjk(&sliver, &point).expect("shapes are apart");
+    assert!(
+        (info.distance - 0.01).abs() <= 0.0001,
+        "distance {}",
+        info.distance
+    );
+    assert_vec_near(info.point_a, Vec3::new(0.0, -0.0002, 0.01), 0.0001);
+}
+
+#[test]
+fn sphere_sphere_distance_and_margin() {
+    let dir = Vec3::new(1.0, 2.0, 3.0).normalize();
+    let a = CollisionShape::new_sphere(Vec3::ZERO, 1.0);
+    let b = CollisionShape::new_sphere(dir * 4.0, 0.5);
+    let info = DistanceInfo::new_with_gjk(&a, &b).expect("spheres are apart");
+    assert!(
+        (info.distance - 2.5).abs() <= DEPTH_TOLERANCE,
+        "distance {}",
+        info.distance
+    );
+    assert_vec_near(info.point_a, dir, DEPTH_TOLERANCE);
+    assert_vec_near(info.point_b, dir * 3.5, DEPTH_TOLERANCE);
+    assert_vec_near(info.normal, dir, DEPTH_TOLERANCE);
+    // The margin grows both spheres, so comes off the distance twice
+    let info = DistanceInfo::new_with_margin(&a, &b, 0.25).expect("spheres are apart");
+    assert!(
+        (info.distance - 2.0).abs() <= DEPTH_TOLERANCE,
+        "distance {}",
+        info.distance
+    );
+    assert_vec_near(info.point_a, dir * 1.25, DEPTH_TOLERANCE);
+    assert_vec_near(info.point_b, dir * 3.25, DEPTH_TOLERANCE);
+    // Overlapping margins alone still count as apart
+    let info = DistanceInfo::new_with_margin(&a, &b, 1.5).expect("spheres are apart");
+    assert!(
+        (info.distance + 0.5).abs() <= DEPTH_TOLERANCE,
+        "distance {}",
+        info.distance
+    );
+    let overlapping = CollisionShape::new_sphere(dir * 1.2, 0.5);
+    assert!(DistanceInfo::new_with_gjk(&a, &overlapping).is_none());
+    assert!(DistanceInfo::new_with_margin(&a, &overlapping, 0.25).is_none());
+}
+
+#[test]
+fn capsule_end_closest_to_a_box_corner() {
+    // The capsule points away from the box's top corner, so its near end is
+    // the closest point
+    let capsule = CollisionShape::new_capsule(Vec3::ZERO, Vec3::splat(1.5), 0.25);
+    let physics = static_world(vec![
+        (cuboid(Vec3::splat(0.5)), at(Vec3::ZERO)),
+        (capsule.clone(), at(Vec3::splat(1.5))),
+        (capsule, at(Vec3::splat(0.4))),
+    ]);
+    let handle = |id| physics.handle_of(Entity::new(id)).expect("body is gone");
+    let (cube, apart, overlapping) = (handle(0), handle(1), handle(2));
+    let normal = Vec3::ONE.normalize();
+    let corner = Vec3::splat(0.5);
+    let gap = 3.0f32.sqrt() - 0.25;
+    let info = physics
+        .body_distance(cube, apart, 0.0)
+        .expect("bodies are apart");
+    assert!(
+        (info.distance - gap).abs() <= DEPTH_TOLERANCE,
+        "distance {}",
+        info.distance
+    );
+    assert_vec_near(info.point_a, corner, DEPTH_TOLERANCE);
+    assert_vec_near(
+        info.point_b,
+        Vec3::splat(1.5) - normal * 0.25,
+        DEPTH_TOLERANCE,
+    );
+    assert_vec_near(info.normal, normal, DEPTH_TOLERANCE);
+    let info = physics
+        .body_distance(cube, apart, 0.1)
+        .expect("bodies are apart");
+    assert!(
+        (info.distance - (gap - 0.2)).abs() <= DEPTH_TOLERANCE,
+        "distance {}",
+        info.distance
+    );
+    assert_vec_near(info.point_a, corner + normal * 0.1, DEPTH_TOLERANCE);
+    assert!(physics.body_distance(cube, overlapping, 0.0).is_none());
+}
+
+// Drops a small ccd ball onto a thin floor and returns where it settles
+fn ccd_ball_on_rect(speed: f32) -> Vec3 {
+    let filter = CollisionFilter::new(1, 0);
+    let mut physics = PhysicsManager::new();
+    let floor = RigidBody::new(
+        BodyType::Static,
+        Arc::new(CollisionShape::new_rect(
+            Vec3::ZERO,
+            Vec3::X * 5.0,
+            Vec3::NEG_Z * 5.0,
+        )),
+        Orientation::new(),
+        Kinematics::new(),
+        false,
+        1.0,
+        filter,
+    );
+    let mut ball = RigidBody::new(
+        BodyType::Dynamic,
+        Arc::new(CollisionShape::new_sphere(Vec3::ZERO, 0.1)),
+        Orientation {
+            translation: Vec3::Y,
+            rotation: Mat4::IDENTITY,
+        },
+        Kinematics::new(),
+        false,
+        1.0,
+        filter,
+    );
+    ball.kinematics.velocity = Vec3::NEG_Y * speed;
+    ball.ccd = true;
+    physics
+        .add_body(Entity::new(0), floor)
+        .expect("adding body failed");
+    physics
+        .add_body(Entity::new(1), ball)
+        .expect("adding body failed");
+    for _ in 0..240 {
+        physics.step();
+    }
+    physics.rigid_bodies()[1].orient.translation
+}
+
+#[test]
+fn fast_ccd_ball_rests_on_rect() {
+    for speed in [50.0, 200.0] {
+        let rest = ccd_ball_on_rect(speed);
+        assert!((rest.y - 0.1).abs() <= 0.01, "{speed} m/s ended at {rest}");
+    }
+}
+
+#[test]
+fn box_slides_to_rest_on_a_big_floor() {
+    // Big enough that tolerances scaled by the floor's size would swallow
+    // the resting depth and lose the contact
+    let filter = CollisionFilter::new(1, 0);
+    let mut physics = PhysicsManager::new();
+    let floor = RigidBody::new(
+        BodyType::Static,
+        Arc::new(cuboid(Vec3::new(100.0, 0.5, 100.0))),
+        Orientation {
+            translation: Vec3::NEG_Y * 0.5,
+            rotation: Mat4::IDENTITY,
+        },
+        Kinematics::new(),
+        false,
+        1.0,
+        filter,
+    );
+    let mut cube = RigidBody::new(
+        BodyType::Dynamic,
+        Arc::new(cuboid(Vec3::splat(0.5))),
+        Orientation {
+            translation: Vec3::Y * 0.495,
+            rotation: Mat4::IDENTITY,
+        },
+        Kinematics::new(),
+        false,
+        1.0,
+        filter,
+    );
+    cube.kinematics.velocity = Vec3::X * 4.0;
+    physics
+        .add_body(Entity::new(0), floor)
+        .expect("adding body failed");
+    let cube = physics
+        .add_body(Entity::new(1), cube)
+        .expect("adding body failed");
+    // Friction of 0.4 against gravity of 10 stops it within a second
+    for step in 0..480 {
+        physics.step();
+        let velocity = physics
+            .body(cube)
+            .expect("cube is there")
+            .kinematics
+            .velocity;
+        if !physics.body(cube).expect("cube is there").is_sleeping() {
+            assert!(
+                physics.step_stats().touches > 0,
+                "no contact at step {step}"
+            );
+        }
+        assert!(velocity.y.abs() < 0.1, "step {step} velocity {velocity}");
+    }
+    let cube = physics.body(cube).expect("cube is there");
+    assert!(
+        cube.kinematics.velocity.length() < 0.01,
+        "{:?}",
+        cube.kinematics
+    );
+    assert!(
+        (cube.orient.translation.y - 0.5).abs() < 0.01,
+        "{}",
+        cube.orient.translation
+    );
+    assert!(
+        (cube.orient.translation.x - 2.0).abs() < 0.2,
+        "{}",
+        cube.orient.translation
+    );
+}
+
+// Static bodies with the given shapes and positions, entity ids counting up
+// from 0
+fn static_world(bodies: Vec<(CollisionShape, Orientation)>) -> PhysicsManager {
+    let mut physics = PhysicsManager::new();
+    for (id, (shape, orient)) in bodies.into_iter().enumerate() {
+        let rb = RigidBody::new(
+            BodyType::Static,
+            Arc::new(shape),
+            orient,
+            Kinematics::new(),
+            false,
+            1.0,
+            CollisionFilter::new(1, 0),
+        );
+        physics
+            .add_body(Entity::new(id as u64), rb)
+            .expect("adding body failed");
+    }
+    physics
+}
+
+fn at(translation: Vec3) -> Orientation {
+    Orientation {
+        translation,
+        rotation: Mat4::IDENTITY,
+    }
+}
+
+#[test]
+fn raycast_hits_the_near_side_of_a_sphere() {
+    let physics = static_world(vec![(
+        CollisionShape::new_sphere(Vec3::ZERO, 1.0),
+        at(Vec3::new(0.0, 0.5, 4.0)),
+    )]);
+    let hit = physics
+        .raycast(Vec3::ZERO, Vec3::Z, 10.0, &CollisionFilter::new(1, 0))
+        .expect("ray hits the sphere");
+    // Enters where z is 4 - sqrt(1 - 0.5^2)
+    let entry = 4.0 - 0.75f32.sqrt();
+    assert_eq!(hit.entity, Entity::new(0));
+    assert!(
+        (hit.fraction - entry / 10.0).abs() <= DEPTH_TOLERANCE,
+        "{hit:?}"
+    );
+    assert_vec_near(hit.point, Vec3::Z * entry, DEPTH_TOLERANCE);
+    let normal = (Vec3::Z * entry - Vec3::new(0.0, 0.5, 4.0)).normalize();
+    assert_vec_near(hit.normal, normal, ROUND_DIR_TOLERANCE);
+}
+
+#[test]
+fn raycast_all_reports_every_body_nearest_first() {
+    // Added out of order, with one box off to the side of the ray
+    let unit_box = || cuboid(Vec3::splat(0.5));
+    let physics = static_world(vec![
+        (unit_box(), at(Vec3::X * 8.0)),
+        (unit_box(), at(Vec3::X * 2.0)),
+        (unit_box(), at(Vec3::new(5.0, 2.0, 0.0))),
+        (unit_box(), at(Vec3::X * 5.0)),
+    ]);
+    let filter = CollisionFilter::new(1, 0);
+    let hits = physics.raycast_all(Vec3::ZERO, Vec3::X, 10.0, &filter);
+    let entities: Vec<_> = hits.iter().map(|hit| hit.entity).collect();
+    assert_eq!(entities, [Entity::new(1), Entity::new(3), Entity::new(0)]);
+    for (hit, near_face) in hits.iter().zip([1.5, 4.5, 7.5]) {
+        assert!(
+            (hit.fraction - near_face / 10.0).abs() <= DEPTH_TOLERANCE,
+            "{hit:?}"
+        );
+        assert_vec_near(hit.normal, Vec3::NEG_X, DEPTH_TOLERANCE);
+    }
+    // Stopping short of the last box leaves it out
+    let short = physics.raycast_all(Vec3::ZERO, Vec3::X, 6.0, &filter);
+    assert_eq!(short.len(), 2, "{short:?}");
+}
+
+#[test]
+fn raycast_normal_follows_a_rotated_box() {
+    // Tilted 30 degrees around z, so its top face leans towards -x
+    let tilt = FRAC_PI_2 / 3.0;
+    let physics = static_world(vec![(
+        cuboid(Vec3::splat(0.5)),
+        Orientation {
+            translation: Vec3::ZERO,
+            rotation: Mat4::from_rotation_z(tilt),
+        },
+    )]);
+    let hit = physics
+        .raycast(
+            Vec3::Y * 5.0,
+            Vec3::NEG_Y,
+            10.0,
+            &CollisionFilter::new(1, 0),
+        )
+        .expect("ray hits the box");
+    // The top face crosses the y axis at 0.5 / cos(tilt)
+    let top = 0.5 / tilt.cos();
+    assert!(
+        (hit.fraction - (5.0 - top) / 10.0).abs() <= DEPTH_TOLERANCE,
+        "{hit:?}"
+    );
+    assert_vec_near(hit.point, Vec3::Y * top, DEPTH_TOLERANCE);
+    assert_vec_near(
+        hit.normal,
+        Vec3::new(-tilt.sin(), tilt.cos(), 0.0),
+        DEPTH_TOLERANCE,
+    );
+}
+
+#[test]
+fn shape_cast_stops_a_sphere_at_a_box_face() {
+    let physics = static_world(vec![(cuboid(Vec3::splat(0.5)), at(Vec3::X * 4.0))]);
+    let ball = CollisionShape::new_sphere(Vec3::ZERO, 0.5);
+    let filter = CollisionFilter::new(1, 0);
+    let hit = physics
+        .shape_cast(&ball, &at(Vec3::Y * 0.2), Vec3::X, 10.0, &filter)
+        .expect("sphere hits the box");
+    // Its front reaches the face at x 3.5 after moving 3
+    assert_eq!(hit.entity, Entity::new(0));
+    assert!((hit.fraction - 0.3).abs() <= DEPTH_TOLERANCE, "{hit:?}");
+    assert_vec_near(hit.point, Vec3::new(3.5, 0.2, 0.0), ROUND_DIR_TOLERANCE);
+    assert_vec_near(hit.normal, Vec3::NEG_X, ROUND_DIR_TOLERANCE);
+    // Passing above the box misses it
+    let over = physics.shape_cast(&ball, &at(Vec3::Y * 1.1), Vec3::X, 10.0, &filter);
+    assert!(over.is_none(), "{over:?}");
+    // Too short to get there
+    let short = physics.shape_cast(&ball, &at(Vec3::Y * 0.2), Vec3::X, 2.5, &filter);
+    assert!(short.is_none(), "{short:?}");
+}
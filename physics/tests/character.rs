@@ -0,0 +1,142 @@
+// Walking the character controller over steps, ledges and slopes
+
+mod support;
+
+use std::sync::Arc;
+
+use common::Entity;
+use glam::{Mat4, Vec3};
+use physics::{
+    BodyType, CollisionFilter, Kinematics, Orientation, PhysicsManager, RigidBody,
+    character::CharacterController, collision_shape::CollisionShape,
+};
+use support::cuboid;
+
+const DT: f32 = 1.0 / 60.0;
+const RADIUS: f32 = 0.3;
+const HALF_HEIGHT: f32 = 0.6;
+// Center height of the capsule standing on the floor
+const STANDING: f32 = RADIUS + HALF_HEIGHT;
+
+fn add_static(physics: &mut PhysicsManager, id: u64, shape: CollisionShape, at: Mat4) {
+    let (_, rotation, translation) = at.to_scale_rotation_translation();
+    let rb = RigidBody::new(
+        BodyType::Static,
+        Arc::new(shape),
+        Orientation {
+            translation,
+            rotation: Mat4::from_quat(rotation),
+        },
+        Kinematics::new(),
+        false,
+        1.0,
+        CollisionFilter::new(1, 0),
+    );
+    physics
+        .add_body(Entity::new(id), rb)
+        .expect("adding body failed");
+}
+
+// Floor with its top at y 0 and a block whose top is at height, spanning
+// x from -1 to 1
+fn floor_with_block(height: f32) -> PhysicsManager {
+    let mut physics = PhysicsManager::new();
+    add_static(
+        &mut physics,
+        0,
+        cuboid(Vec3::new(20.0, 0.5, 20.0)),
+        Mat4::from_translation(Vec3::NEG_Y * 0.5),
+    );
+    add_static(
+        &mut physics,
+        1,
+        cuboid(Vec3::new(1.0, height * 0.5, 1.0)),
+        Mat4::from_translation(Vec3::Y * height * 0.5),
+    );
+    physics
+}
+
+fn walk(
+    physics: &PhysicsManager,
+    character: &mut CharacterController,
+    velocity: Vec3,
+    steps: usize,
+) {
+    for _ in 0..steps {
+        physics.move_character(character, velocity, DT);
+    }
+}
+
+#[test]
+fn steps_up_onto_a_low_ledge() {
+    let physics = floor_with_block(0.2);
+    let mut character =
+        CharacterController::new(Vec3::new(3.0, STANDING, 0.0), RADIUS, HALF_HEIGHT);
+    walk(&physics, &mut character, Vec3::NEG_X * 4.0, 45);
+
+    assert!(
+        character.position.x < 0.5,
+        "stuck at {}",
+        character.position
+    );
+    assert!(
+        (character.feet().y - 0.2).abs() < 0.05,
+        "feet at {}",
+        character.feet()
+    );
+    assert!(character.is_on_ground());
+}
+
+#[test]
+fn stops_at_a_ledge_taller_than_a_step() {
+    let physics = floor_with_block(0.4);
+    let mut character =
+        CharacterController::new(Vec3::new(3.0, STANDING, 0.0), RADIUS, HALF_HEIGHT);
+    walk(&physics, &mut character, Vec3::NEG_X * 4.0, 45);
+
+    assert!(
+        character.position.x > 1.0 + RADIUS - 0.05,
+        "walked to {}",
+        character.position
+    );
+    assert!(character.feet().y < 0.05, "feet at {}", character.feet());
+    assert!(character.is_on_ground());
+}
+
+#[test]
+fn stands_on_the_edge_of_a_ledge() {
+    let physics = floor_with_block(0.5);
+    // Hanging a little past the edge, still over the corner
+    let mut character = CharacterController::new(
+        Vec3::new(1.1, 0.5 + STANDING + 0.05, 0.0),
+        RADIUS,
+        HALF_HEIGHT,
+    );
+    walk(&physics, &mut character, Vec3::ZERO, 30);
+
+    assert!(character.is_on_ground());
+    assert!(character.feet().y > 0.4, "fell to {}", character.feet());
+}
+
+#[test]
+fn slides_down_a_slope_too_steep_to_stand_on() {
+    let mut physics = PhysicsManager::new();
+    add_static(
+        &mut physics,
+        0,
+        cuboid(Vec3::new(20.0, 0.5, 20.0)),
+        Mat4::from_translation(Vec3::NEG_Y * 0.5),
+    );
+    // Facing -x, 60 degrees steep, its surface above x 1 at y 2.73
+    add_static(
+        &mut physics,
+        1,
+        cuboid(Vec3::new(4.0, 0.5, 4.0)),
+        Mat4::from_rotation_z(60f32.to_radians()),
+    );
+    let mut character = CharacterController::new(Vec3::new(1.0, 4.0, 0.0), RADIUS, HALF_HEIGHT);
+    walk(&physics, &mut character, Vec3::ZERO, 180);
+
+    assert!(character.is_on_ground());
+    assert!(character.feet().y < 0.05, "feet at {}", character.feet());
+}
@@ -0,0 +1,34 @@
+// Hulls simplified down to a vertex budget
+
+use glam::Vec3;
+use physics::collision_shape::CollisionShape;
+
+// Evenly spread over the unit sphere, which holds about 4.19
+fn sphere_points(n: usize) -> Vec<Vec3> {
+    (0..n)
+        .map(|i| {
+            let y = 1.0 - 2.0 * (i as f32 + 0.5) / n as f32;
+            let r = (1.0 - y * y).sqrt();
+            let angle = i as f32 * 2.399963;
+            Vec3::new(r * angle.cos(), y, r * angle.sin())
+        })
+        .collect()
+}
+
+#[test]
+fn simplified_hull_fills_its_vertex_budget() {
+    let points = sphere_points(200);
+    // Taking the farthest point first has to keep more of the sphere than
+    // whatever point happens to come first
+    for (max, min_volume) in [(8, 1.5), (12, 2.3)] {
+        let hull = CollisionShape::new_convex_hull(&points, Some(max)).expect("hull failed");
+        let CollisionShape::Mesh(mesh) = &hull else {
+            panic!("hull isn't a mesh");
+        };
+        assert_eq!(mesh.points().len(), max);
+        let volume = hull.mass_properties(1.0).volume;
+        assert!(volume >= min_volume, "{max} vertices hold {volume}");
+    }
+    let full = CollisionShape::new_convex_hull(&points, None).expect("hull failed");
+    assert!(full.mass_properties(1.0).volume >= 4.0);
+}
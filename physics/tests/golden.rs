@@ -0,0 +1,70 @@
+// Drops the bodies of the levels in tests/levels and checks where they come
+// to rest against tests/golden. Run with UPDATE_GOLDEN=1 to rewrite the golden
+// files after an intended change in behavior.
+
+use std::{fs, path::PathBuf};
+
+use common::Level;
+use glam::Vec3;
+use physics::{PhysicsConfig, PhysicsManager};
+
+const FRAME_MS: u32 = 16;
+const FRAMES: u32 = 300;
+const POSITION_TOLERANCE: f32 = 0.01;
+
+fn test_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests")
+}
+
+fn resting_positions(level_name: &str) -> Vec<[f32; 3]> {
+    let level_path = test_dir().join(format!("levels/{level_name}.ron"));
+    let level = Level::from_file(level_path.to_str().expect("non utf-8 test path"))
+        .expect("loading level failed");
+    let mut physics =
+        PhysicsManager::from_level(&level, PhysicsConfig::default()).expect("level import failed");
+    for _ in 0..FRAMES {
+        physics.update(FRAME_MS as f32 / 1000.0);
+    }
+    physics
+        .rigid_bodies()
+        .values()
+        .map(|rb| rb.orient.translation.to_array())
+        .collect()
+}
+
+fn check_golden(level_name: &str) {
+    let positions = resting_positions(level_name);
+    let golden_path = test_dir().join(format!("golden/{level_name}.ron"));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        let golden = ron::ser::to_string_pretty(&positions, Default::default())
+            .expect("serializing golden failed");
+        fs::write(&golden_path, golden).expect("writing golden failed");
+        return;
+    }
+    let golden: Vec<[f32; 3]> =
+        ron::from_str(&fs::read_to_string(&golden_path).expect("golden file missing"))
+            .expect("parsing golden failed");
+    assert_eq!(positions.len(), golden.len(), "body count changed");
+    for (i, (actual, expected)) in positions.into_iter().zip(golden).enumerate() {
+        let (actual, expected) = (Vec3::from_array(actual), Vec3::from_array(expected));
+        assert!(
+            actual.distance(expected) <= POSITION_TOLERANCE,
+            "{level_name}: body {i} rests at {actual}, expected {expected}"
+        );
+    }
+}
+
+#[test]
+fn box_drop() {
+    check_golden("box_drop");
+}
+
+#[test]
+fn box_stack() {
+    check_golden("box_stack");
+}
+
+#[test]
+fn shape_drop() {
+    check_golden("shape_drop");
+}
@@ -0,0 +1,229 @@
+// Joints that can't be solved get turned down when they are made, and the
+// ones that can hold the bodies the way they say
+
+mod support;
+
+use std::sync::Arc;
+
+use common::Entity;
+use glam::{Mat4, Quat, Vec3};
+use physics::{
+    BodyType, CollisionFilter, Kinematics, Orientation, PhysicsManager, RigidBody,
+    collision_shape::CollisionShape,
+    joint::{Joint, JointKind, JointMotor},
+};
+use support::cuboid;
+
+// Steps in two seconds at the default rate
+const TWO_SECONDS: usize = 480;
+
+// Two balls side by side, a at -1 and b at 1 on x
+fn two_balls() -> PhysicsManager {
+    let mut physics = PhysicsManager::new();
+    for (id, x) in [(0, -1.0), (1, 1.0)] {
+        let ball = RigidBody::new(
+            BodyType::Dynamic,
+            Arc::new(CollisionShape::new_sphere(Vec3::ZERO, 0.5)),
+            Orientation {
+                translation: Vec3::X * x,
+                rotation: Mat4::IDENTITY,
+            },
+            Kinematics::new(),
+            true,
+            1.0,
+            CollisionFilter::new(1, 0),
+        );
+        physics
+            .add_body(Entity::new(id), ball)
+            .expect("adding body failed");
+    }
+    physics
+}
+
+fn make(physics: &PhysicsManager, kind: JointKind) -> anyhow::Result<Joint> {
+    Joint::new(
+        physics.rigid_bodies(),
+        Entity::new(0),
+        Entity::new(1),
+        Vec3::ZERO,
+        Vec3::ZERO,
+        kind,
+    )
+}
+
+#[test]
+fn limits_have_to_be_in_order() {
+    let physics = two_balls();
+    for (limits, ok) in [
+        ([-1.0, 1.0], true),
+        ([0.5, 0.5], true),
+        ([1.0, -1.0], false),
+    ] {
+        let hinge = JointKind::Hinge {
+            axis: Vec3::Y,
+            limits: Some(limits),
+            motor: None,
+        };
+        let slider = JointKind::Prismatic {
+            axis: Vec3::X,
+            limits: Some(limits),
+            motor: None,
+        };
+        assert_eq!(make(&physics, hinge).is_ok(), ok, "hinge {limits:?}");
+        assert_eq!(make(&physics, slider).is_ok(), ok, "slider {limits:?}");
+    }
+}
+
+#[test]
+fn distance_range_has_to_be_in_order() {
+    let physics = two_balls();
+    assert!(make(&physics, JointKind::Distance { min: 1.0, max: 3.0 }).is_ok());
+    assert!(make(&physics, JointKind::Distance { min: 3.0, max: 1.0 }).is_err());
+}
+
+#[test]
+fn motor_force_cant_be_negative() {
+    let physics = two_balls();
+    for (max_force, ok) in [(10.0, true), (0.0, true), (-10.0, false)] {
+        let hinge = JointKind::Hinge {
+            axis: Vec3::Y,
+            limits: None,
+            motor: Some(JointMotor::new(1.0, max_force)),
+        };
+        assert_eq!(make(&physics, hinge).is_ok(), ok, "max force {max_force}");
+    }
+}
+
+// Static post a at the origin and a dynamic box b at b_at, joined by kind at
+// anchor. b's gravity is scaled by gravity_scale.
+fn post_and_box(b_at: Vec3, anchor: Vec3, kind: JointKind, gravity_scale: f32) -> PhysicsManager {
+    let mut physics = PhysicsManager::new();
+    let body = |body_type, half: f32, at: Vec3, gravity_scale| {
+        RigidBody::new(
+            body_type,
+            Arc::new(cuboid(Vec3::splat(half))),
+            Orientation {
+                translation: at,
+                rotation: Mat4::IDENTITY,
+            },
+            Kinematics::new(),
+            true,
+            gravity_scale,
+            CollisionFilter::new(1, 0),
+        )
+    };
+    let post = body(BodyType::Static, 0.1, Vec3::ZERO, 1.0);
+    let cube = body(BodyType::Dynamic, 0.25, b_at, gravity_scale);
+    physics
+        .add_body(Entity::new(0), post)
+        .expect("adding body failed");
+    physics
+        .add_body(Entity::new(1), cube)
+        .expect("adding body failed");
+    let joint = Joint::new(
+        physics.rigid_bodies(),
+        Entity::new(0),
+        Entity::new(1),
+        anchor,
+        anchor,
+        kind,
+    )
+    .expect("making joint failed");
+    physics.joints.push(joint);
+    physics
+}
+
+fn cube(physics: &PhysicsManager) -> &RigidBody {
+    &physics.rigid_bodies()[1]
+}
+
+// How far b has turned around y
+fn yaw(physics: &PhysicsManager) -> f32 {
+    let (axis, angle) = Quat::from_mat4(&cube(physics).orient.rotation).to_axis_angle();
+    axis.y * angle
+}
+
+#[test]
+fn ball_socket_pendulum_keeps_its_length() {
+    let mut physics = post_and_box(Vec3::X * 2.0, Vec3::ZERO, JointKind::BallSocket, 1.0);
+    let mut lowest = f32::INFINITY;
+    for step in 0..TWO_SECONDS {
+        physics.step();
+        let at = cube(&physics).orient.translation;
+        assert!((at.length() - 2.0).abs() <= 0.05, "step {step} at {at}");
+        lowest = lowest.min(at.y);
+    }
+    // It actually swung
+    assert!(lowest < -1.9, "lowest {lowest}");
+}
+
+#[test]
+fn hinge_motor_reaches_its_speed() {
+    let hinge = JointKind::Hinge {
+        axis: Vec3::Y,
+        limits: None,
+        motor: Some(JointMotor::new(2.0, 100.0)),
+    };
+    let mut physics = post_and_box(Vec3::X, Vec3::X, hinge, 0.0);
+    for _ in 0..TWO_SECONDS / 2 {
+        physics.step();
+    }
+    let spin = cube(&physics).kinematics.angular_velocity;
+    assert!(spin.distance(Vec3::Y * 2.0) <= 0.05, "spinning at {spin}");
+}
+
+#[test]
+fn hinge_limits_stop_the_motor() {
+    for target in [5.0, -5.0] {
+        let hinge = JointKind::Hinge {
+            axis: Vec3::Y,
+            limits: Some([-0.5, 0.5]),
+            motor: Some(JointMotor::new(target, 1.0)),
+        };
+        let mut physics = post_and_box(Vec3::X, Vec3::X, hinge, 0.0);
+        for _ in 0..TWO_SECONDS {
+            physics.step();
+        }
+        let expected = 0.5 * f32::signum(target);
+        assert!(
+            (yaw(&physics) - expected).abs() <= 0.05,
+            "driven at {target} ended at {}",
+            yaw(&physics)
+        );
+    }
+}
+
+#[test]
+fn prismatic_limits_stop_a_falling_box() {
+    // Gravity pulls b down the axis one way round, and up it the other
+    for (axis, bound) in [(Vec3::Y, -1.0), (Vec3::NEG_Y, 0.5)] {
+        let slider = JointKind::Prismatic {
+            axis,
+            limits: Some([-1.0, 0.5]),
+            motor: None,
+        };
+        let mut physics = post_and_box(Vec3::X, Vec3::X, slider, 1.0);
+        for _ in 0..TWO_SECONDS {
+            physics.step();
+        }
+        let at = cube(&physics).orient.translation;
+        let expected = Vec3::X + axis * bound;
+        assert!(at.distance(expected) <= 0.05, "{axis} slider ended at {at}");
+    }
+}
+
+#[test]
+fn fixed_joint_holds_a_box_out_under_gravity() {
+    let mut physics = post_and_box(Vec3::X * 1.5, Vec3::X * 0.75, JointKind::Fixed, 1.0);
+    for _ in 0..TWO_SECONDS {
+        physics.step();
+    }
+    let rb = cube(&physics);
+    assert!(
+        rb.orient.translation.distance(Vec3::X * 1.5) <= 0.05,
+        "sagged to {}",
+        rb.orient.translation
+    );
+    let turned = Quat::from_mat4(&rb.orient.rotation).angle_between(Quat::IDENTITY);
+    assert!(turned <= 0.05, "turned by {turned}");
+}
@@ -0,0 +1,91 @@
+// Older level files have to keep loading as the format grows
+
+use common::{Level, Node, PhysicsRb};
+
+fn bodies(level: &str) -> Vec<PhysicsRb> {
+    let level: Level = ron::from_str(level).expect("parsing level failed");
+    level
+        .nodes
+        .into_iter()
+        .filter_map(|node| match node {
+            Node::PhysicsRb(rb) => Some(rb),
+            Node::Joint(_) => None,
+        })
+        .collect()
+}
+
+#[test]
+fn gravity_scale_defaults_and_accepts_has_gravity() {
+    let rbs = bodies(
+        r#"(nodes: [
+            PhysicsRb((
+                mass: 1,
+                shape: Sphere(c: (0, 0, 0), r: 0.5),
+                init_location: (0, 0, 0),
+                no_interact_mask: 0,
+            )),
+            PhysicsRb((
+                mass: 1,
+                shape: Sphere(c: (0, 0, 0), r: 0.5),
+                has_gravity: false,
+                init_location: (0, 0, 0),
+                no_interact_mask: 0,
+            )),
+            PhysicsRb((
+                mass: 1,
+                shape: Sphere(c: (0, 0, 0), r: 0.5),
+                has_gravity: true,
+                init_location: (0, 0, 0),
+                no_interact_mask: 0,
+            )),
+            PhysicsRb((
+                mass: 1,
+                shape: Sphere(c: (0, 0, 0), r: 0.5),
+                gravity_scale: 0.5,
+                init_location: (0, 0, 0),
+                no_interact_mask: 0,
+            )),
+        ])"#,
+    );
+    let scales: Vec<_> = rbs.iter().map(|rb| rb.gravity_scale).collect();
+    assert_eq!(scales, [1.0, 0.0, 1.0, 0.5]);
+}
+
+#[test]
+fn ignore_mask_defaults_and_accepts_no_interact_mask() {
+    let rbs = bodies(
+        r#"(nodes: [
+            PhysicsRb((
+                mass: 1,
+                shape: Sphere(c: (0, 0, 0), r: 0.5),
+                init_location: (0, 0, 0),
+            )),
+            PhysicsRb((
+                mass: 1,
+                shape: Sphere(c: (0, 0, 0), r: 0.5),
+                init_location: (0, 0, 0),
+                no_interact_mask: 0b100,
+            )),
+            PhysicsRb((
+                mass: 1,
+                shape: Sphere(c: (0, 0, 0), r: 0.5),
+                init_location: (0, 0, 0),
+                category: 0b10,
+                ignore: 0b10,
+            )),
+        ])"#,
+    );
+    let filters: Vec<_> = rbs.iter().map(|rb| (rb.category, rb.ignore)).collect();
+    assert_eq!(filters, [(1, 0), (1, 0b100), (0b10, 0b10)]);
+    // Both spellings at once are the same field twice
+    let both = r#"(nodes: [
+        PhysicsRb((
+            mass: 1,
+            shape: Sphere(c: (0, 0, 0), r: 0.5),
+            init_location: (0, 0, 0),
+            no_interact_mask: 0b1,
+            ignore: 0b1,
+        )),
+    ])"#;
+    assert!(ron::from_str::<Level>(both).is_err());
+}
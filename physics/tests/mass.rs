@@ -0,0 +1,243 @@
+// Mass properties of shapes with a known closed form, and how levels pick
+// between mass and density
+
+mod support;
+
+use std::sync::Arc;
+
+use common::Level;
+use glam::{Mat3, Quat, Vec3};
+use physics::{
+    BodyType, CollisionFilter, Kinematics, Orientation, PhysicsConfig, PhysicsManager, RigidBody,
+    collision_shape::CollisionShape,
+};
+use support::{assert_vec_near, cuboid, placed};
+
+const TOLERANCE: f32 = 0.001;
+
+fn assert_near(actual: f32, expected: f32) {
+    assert!(
+        (actual - expected).abs() <= TOLERANCE * expected.abs().max(1.0),
+        "expected {expected}, got {actual}"
+    );
+}
+
+fn assert_diagonal(inertia: Mat3, expected: Vec3) {
+    assert_vec_near(
+        Vec3::new(inertia.x_axis.x, inertia.y_axis.y, inertia.z_axis.z),
+        expected,
+        TOLERANCE * expected.length(),
+    );
+    assert_vec_near(
+        Vec3::new(inertia.y_axis.x, inertia.z_axis.x, inertia.z_axis.y),
+        Vec3::ZERO,
+        TOLERANCE,
+    );
+}
+
+#[test]
+fn box_from_density() {
+    let shape = placed(
+        &cuboid(Vec3::new(1.0, 0.5, 2.0)),
+        Vec3::new(1.0, 2.0, 3.0),
+        Quat::IDENTITY,
+    );
+    let props = shape.mass_properties(2.0);
+    assert_near(props.volume, 8.0);
+    assert_near(props.mass, 16.0);
+    assert_vec_near(props.center, Vec3::new(1.0, 2.0, 3.0), TOLERANCE);
+    let m = props.mass / 12.0;
+    assert_diagonal(
+        props.inertia,
+        Vec3::new(m * (1.0 + 16.0), m * (4.0 + 16.0), m * (4.0 + 1.0)),
+    );
+}
+
+#[test]
+fn pyramid_center_is_not_its_vertex_average() {
+    // Square base 2 wide, apex 3 up. The vertices average at 0.6 up while the
+    // mass sits a quarter of the way up.
+    let points = [
+        Vec3::new(1.0, 0.0, 1.0),
+        Vec3::new(-1.0, 0.0, 1.0),
+        Vec3::new(-1.0, 0.0, -1.0),
+        Vec3::new(1.0, 0.0, -1.0),
+        Vec3::new(0.0, 3.0, 0.0),
+    ];
+    let shape = CollisionShape::new_convex_hull(&points, None).expect("hull failed");
+    assert_vec_near(shape.center_hint(), Vec3::new(0.0, 0.6, 0.0), TOLERANCE);
+    let props = shape.mass_properties(1.0);
+    assert_near(props.volume, 4.0);
+    assert_vec_near(props.center, Vec3::new(0.0, 0.75, 0.0), TOLERANCE);
+    let (a, h) = (2.0f32, 3.0f32);
+    let side = props.mass * (a * a / 20.0 + 3.0 * h * h / 80.0);
+    assert_diagonal(
+        props.inertia,
+        Vec3::new(side, props.mass * a * a / 10.0, side),
+    );
+}
+
+#[test]
+fn compound_adds_up_its_parts() {
+    let small = cuboid(Vec3::splat(0.5));
+    let big = placed(&cuboid(Vec3::splat(1.0)), Vec3::X * 3.0, Quat::IDENTITY);
+    let shape = CollisionShape::new_compound(vec![
+        (Default::default(), small.clone()),
+        (Default::default(), big.clone()),
+    ])
+    .expect("building compound failed");
+    let props = shape.mass_properties(1.0);
+    assert_near(props.mass, 9.0);
+    let center = Vec3::X * 24.0 / 9.0;
+    assert_vec_near(props.center, center, TOLERANCE);
+    // Around x the parts share an axis, around y and z they get shifted on
+    let (small_props, big_props) = (small.mass_properties(1.0), big.mass_properties(1.0));
+    let shift = small_props.mass * center.x.powi(2) + big_props.mass * (3.0 - center.x).powi(2);
+    let own = small_props.inertia.x_axis.x + big_props.inertia.x_axis.x;
+    assert_diagonal(props.inertia, Vec3::new(own, own + shift, own + shift));
+}
+
+#[test]
+fn compound_rejects_triangle_mesh_parts() {
+    // A mesh part would weigh nothing and let a triangle mesh move
+    let quad = CollisionShape::new_triangle_mesh(
+        &[Vec3::X, Vec3::NEG_X, Vec3::Z, Vec3::NEG_Z],
+        &[0, 2, 1, 0, 1, 3],
+    )
+    .expect("building mesh failed");
+    let parts = vec![
+        (Default::default(), cuboid(Vec3::splat(0.5))),
+        (Default::default(), quad),
+    ];
+    assert!(CollisionShape::new_compound(parts).is_err());
+    let level = LEVEL.replace(
+        "shape: Sphere(c: (0, 0, 0), r: 0.5),",
+        "shape: Compound(parts: [
+            Sphere(c: (0, 0, 0), r: 0.5),
+            TriangleMesh(vertices: [(1, 0, 0), (-1, 0, 0), (0, 0, 1)], indices: [0, 2, 1]),
+        ]),",
+    );
+    let level: Level = ron::from_str(&level).expect("parsing level failed");
+    assert!(PhysicsManager::from_level(&level, PhysicsConfig::default()).is_err());
+}
+
+#[test]
+fn with_mass_keeps_the_distribution() {
+    let props = CollisionShape::new_sphere(Vec3::Y, 0.5).mass_properties(3.0);
+    let scaled = props.with_mass(2.0);
+    assert_near(scaled.mass, 2.0);
+    assert_vec_near(scaled.center, Vec3::Y, TOLERANCE);
+    assert_diagonal(scaled.inertia, Vec3::splat(0.4 * 2.0 * 0.25));
+}
+
+#[test]
+fn flat_rect_is_weighed_by_area() {
+    // 2 by 4, weighed as a shell with both of its sides
+    let rect = CollisionShape::new_rect(Vec3::Y, Vec3::X, Vec3::NEG_Z * 2.0);
+    let props = rect.mass_properties(2.0);
+    assert_near(props.volume, 0.0);
+    assert_near(props.mass, 32.0);
+    assert_vec_near(props.center, Vec3::Y, TOLERANCE);
+    let m = props.mass / 12.0;
+    assert_diagonal(props.inertia, Vec3::new(m * 16.0, m * 20.0, m * 4.0));
+}
+
+#[test]
+fn can_rotate_applies_when_changed_later() {
+    let mut rb = RigidBody::new(
+        BodyType::Dynamic,
+        Arc::new(cuboid(Vec3::splat(0.5))),
+        Orientation::new(),
+        Kinematics::new(),
+        false,
+        1.0,
+        CollisionFilter::new(1, 0),
+    );
+    assert_eq!(rb.inv_inertia_world(), Mat3::ZERO);
+    rb.can_rotate = true;
+    assert_diagonal(rb.inv_inertia_world(), Vec3::splat(6.0));
+    rb.can_rotate = false;
+    assert_eq!(rb.inv_inertia_world(), Mat3::ZERO);
+}
+
+const LEVEL: &str = r#"(nodes: [
+    PhysicsRb((
+        mass: inf,
+        shape: Cube(c: (0, 0, 0), x: (5, 0, 0), y: (0, 0, -5), h: 0.5),
+        gravity_scale: 1,
+        init_location: (0, 0, 0),
+        no_interact_mask: 0,
+    )),
+    PhysicsRb((
+        body_type: Some(Kinematic),
+        shape: Sphere(c: (0, 0, 0), r: 0.5),
+        gravity_scale: 1,
+        init_location: (0, 3, 0),
+        no_interact_mask: 0,
+    )),
+    PhysicsRb((
+        density: Some(2),
+        shape: Cube(c: (0, 0, 0), x: (0.5, 0, 0), y: (0, 0, -0.5), h: 0.5),
+        gravity_scale: 1,
+        init_location: (3, 1, 0),
+        no_interact_mask: 0,
+    )),
+    PhysicsRb((
+        mass: 5,
+        shape: Cube(c: (0, 0, 0), x: (0.5, 0, 0), y: (0, 0, -0.5), h: 0.5),
+        gravity_scale: 1,
+        init_location: (-3, 1, 0),
+        no_interact_mask: 0,
+    )),
+])"#;
+
+#[test]
+fn level_bodies_take_mass_or_density() {
+    let level: Level = ron::from_str(LEVEL).expect("parsing level failed");
+    let physics =
+        PhysicsManager::from_level(&level, PhysicsConfig::default()).expect("level import failed");
+    let bodies: Vec<_> = physics.rigid_bodies().values().collect();
+    assert_eq!(bodies[0].body_type, BodyType::Static);
+    assert_eq!(bodies[1].body_type, BodyType::Kinematic);
+    assert_eq!(bodies[0].inv_mass(), 0.0);
+    assert_eq!(bodies[1].inv_mass(), 0.0);
+    assert_eq!(bodies[2].body_type, BodyType::Dynamic);
+    assert_near(bodies[2].mass(), 2.0);
+    assert_near(bodies[3].mass(), 5.0);
+    assert_diagonal(
+        bodies[3].mass_properties().inertia,
+        Vec3::splat(5.0 * 2.0 / 12.0),
+    );
+}
+
+#[test]
+fn level_body_with_mass_and_density_fails() {
+    let level = LEVEL.replace("mass: 5,", "mass: 5, density: Some(1),");
+    let level: Level = ron::from_str(&level).expect("parsing level failed");
+    assert!(PhysicsManager::from_level(&level, PhysicsConfig::default()).is_err());
+}
+
+#[test]
+fn level_body_density_has_to_be_positive_and_finite() {
+    for density in ["0", "-1", "inf", "NaN"] {
+        let level = LEVEL.replace("density: Some(2),", &format!("density: Some({density}),"));
+        let level: Level = ron::from_str(&level).expect("parsing level failed");
+        assert!(
+            PhysicsManager::from_level(&level, PhysicsConfig::default()).is_err(),
+            "density {density}"
+        );
+    }
+}
+
+#[test]
+fn flat_level_body_takes_a_mass_but_not_a_density() {
+    let rect = "shape: Rectangle(c: (0, 0, 0), x: (0.5, 0, 0), y: (0, 0, -0.5)),";
+    let cube = "shape: Cube(c: (0, 0, 0), x: (0.5, 0, 0), y: (0, 0, -0.5), h: 0.5),";
+    let level: Level = ron::from_str(&LEVEL.replace(cube, rect)).expect("parsing level failed");
+    assert!(PhysicsManager::from_level(&level, PhysicsConfig::default()).is_err());
+    let level = LEVEL.replace(cube, rect).replace("density: Some(2),", "");
+    let level: Level = ron::from_str(&level).expect("parsing level failed");
+    let physics =
+        PhysicsManager::from_level(&level, PhysicsConfig::default()).expect("level import failed");
+    assert_near(physics.rigid_bodies()[3].mass(), 5.0);
+}
@@ -0,0 +1,80 @@
+// Properties any pair of boxes has to keep. Boxes only, EPA is exact on
+// polytopes while it only approximates round shapes.
+
+mod support;
+
+use glam::{Quat, Vec3};
+use physics::collision_shape::CollisionShape;
+use proptest::prelude::*;
+use support::{SEPARATION_MARGIN, cuboid, intersect, placed, separates};
+
+const DEPTH_TOLERANCE: f32 = 0.001;
+
+fn vec3(range: std::ops::Range<f32>) -> impl Strategy<Value = Vec3> {
+    (range.clone(), range.clone(), range).prop_map(|(x, y, z)| Vec3::new(x, y, z))
+}
+
+fn rotation() -> impl Strategy<Value = Quat> {
+    // Axis from the direction, angle from the length
+    vec3(-1.0..1.0).prop_filter_map("no rotation axis", |v| {
+        let angle = v.length().min(1.0) * std::f32::consts::PI;
+        v.try_normalize()
+            .map(|axis| Quat::from_axis_angle(axis, angle))
+    })
+}
+
+// Placed close enough to the origin that most pairs overlap
+fn placed_box() -> impl Strategy<Value = CollisionShape> {
+    (vec3(0.1..1.5), vec3(-1.5..1.5), rotation())
+        .prop_map(|(half, center, rotation)| placed(&cuboid(half), center, rotation))
+}
+
+proptest! {
+    #[test]
+    fn swapping_shapes_flips_the_result(a in placed_box(), b in placed_box()) {
+        match (intersect(&a, &b), intersect(&b, &a)) {
+            (Some(ab), Some(ba)) => {
+                let ba = ba.obj_swapped();
+                prop_assert!((ab.dist - ba.dist).abs() <= DEPTH_TOLERANCE, "{ab:?} vs {ba:?}");
+                // Equally deep faces can give different directions, but both
+                // have to push the shapes apart
+                prop_assert!(separates(&a, &b, &ab));
+                prop_assert!(separates(&a, &b, &ba));
+            }
+            // Right at the surface one side may still see a sliver of depth
+            (Some(info), None) | (None, Some(info)) => {
+                prop_assert!(info.dist <= SEPARATION_MARGIN, "{info:?}");
+            }
+            (None, None) => {}
+        }
+    }
+
+    #[test]
+    fn moving_both_shapes_changes_nothing(
+        a in placed_box(),
+        b in placed_box(),
+        offset in vec3(-50.0..50.0),
+    ) {
+        let moved_a = placed(&a, offset, Quat::IDENTITY);
+        let moved_b = placed(&b, offset, Quat::IDENTITY);
+        match (intersect(&a, &b), intersect(&moved_a, &moved_b)) {
+            (Some(info), Some(moved)) => {
+                prop_assert!((info.dist - moved.dist).abs() <= DEPTH_TOLERANCE, "{info:?} vs {moved:?}");
+                prop_assert!(separates(&moved_a, &moved_b, &moved));
+            }
+            (Some(info), None) | (None, Some(info)) => {
+                prop_assert!(info.dist <= SEPARATION_MARGIN, "{info:?}");
+            }
+            (None, None) => {}
+        }
+    }
+
+    #[test]
+    fn found_depth_separates_the_shapes(a in placed_box(), b in placed_box()) {
+        if let Some(info) = intersect(&a, &b) {
+            prop_assert!(info.dist >= 0.0);
+            prop_assert!((info.dir.length() - 1.0).abs() <= DEPTH_TOLERANCE);
+            prop_assert!(separates(&a, &b, &info));
+        }
+    }
+}
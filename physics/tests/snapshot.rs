@@ -0,0 +1,64 @@
+// Restoring a snapshot, even one that went through RON, carries on bit for
+// bit like the run it was taken from
+
+use std::path::PathBuf;
+
+use common::Level;
+use physics::{PhysicsConfig, PhysicsManager, snapshot::PhysicsSnapshot};
+
+// Still falling and bouncing when the snapshot is taken
+const STEPS_BEFORE: usize = 120;
+const STEPS_AFTER: usize = 240;
+
+fn load(level_name: &str) -> PhysicsManager {
+    let level_path =
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(format!("tests/levels/{level_name}.ron"));
+    let level = Level::from_file(level_path.to_str().expect("non utf-8 test path"))
+        .expect("loading level failed");
+    PhysicsManager::from_level(&level, PhysicsConfig::default()).expect("level import failed")
+}
+
+fn run(physics: &mut PhysicsManager, steps: usize) {
+    for _ in 0..steps {
+        physics.step();
+    }
+}
+
+// Bits of every body's orientation and velocities, in body order
+fn state_bits(physics: &PhysicsManager) -> Vec<Vec<u32>> {
+    physics
+        .rigid_bodies()
+        .values()
+        .map(|rb| {
+            rb.orient
+                .translation
+                .to_array()
+                .into_iter()
+                .chain(rb.orient.rotation.to_cols_array())
+                .chain(rb.kinematics.velocity.to_array())
+                .chain(rb.kinematics.angular_velocity.to_array())
+                .map(f32::to_bits)
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn restored_ron_snapshot_matches_the_uninterrupted_run() {
+    for level_name in ["box_stack", "shape_drop"] {
+        let mut physics = load(level_name);
+        run(&mut physics, STEPS_BEFORE);
+        let ron = ron::to_string(&physics.snapshot()).expect("serializing snapshot failed");
+        run(&mut physics, STEPS_AFTER);
+
+        let snapshot: PhysicsSnapshot = ron::from_str(&ron).expect("parsing snapshot failed");
+        let mut restored = PhysicsManager::new();
+        restored.restore(snapshot);
+        run(&mut restored, STEPS_AFTER);
+
+        assert!(
+            state_bits(&physics) == state_bits(&restored),
+            "{level_name} diverged after restoring"
+        );
+    }
+}
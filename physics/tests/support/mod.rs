@@ -0,0 +1,40 @@
+#![allow(dead_code)]
+
+use glam::{Mat4, Quat, Vec3};
+use physics::{Orientation, collision_shape::CollisionShape, intersection_info::IntersectionInfo};
+
+// Moving the shapes apart by the found depth plus this has to separate them
+pub const SEPARATION_MARGIN: f32 = 0.002;
+
+pub fn placed(shape: &CollisionShape, translation: Vec3, rotation: Quat) -> CollisionShape {
+    shape.with_orientation(&Orientation {
+        translation,
+        rotation: Mat4::from_quat(rotation),
+    })
+}
+
+// Box with the given half extents, centered on the origin and axis aligned
+pub fn cuboid(half: Vec3) -> CollisionShape {
+    CollisionShape::new_cube(Vec3::ZERO, Vec3::X * half.x, Vec3::NEG_Z * half.z, half.y)
+}
+
+pub fn intersect(a: &CollisionShape, b: &CollisionShape) -> Option<IntersectionInfo> {
+    IntersectionInfo::new_with_gjk(a, b).expect("intersection test failed")
+}
+
+// Pushing b out of a along info leaves them no more than touching
+pub fn separates(a: &CollisionShape, b: &CollisionShape, info: &IntersectionInfo) -> bool {
+    let pushed = placed(
+        b,
+        info.dir * (info.dist + SEPARATION_MARGIN),
+        Quat::IDENTITY,
+    );
+    intersect(a, &pushed).is_none_or(|rest| rest.dist <= SEPARATION_MARGIN)
+}
+
+pub fn assert_vec_near(actual: Vec3, expected: Vec3, tolerance: f32) {
+    assert!(
+        actual.distance(expected) <= tolerance,
+        "expected {expected}, got {actual}"
+    );
+}
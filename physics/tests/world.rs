@@ -0,0 +1,387 @@
+// How a whole world behaves over a few steps: sleeping, events, handles,
+// collision filters, applied forces and fixed step timing
+
+mod support;
+
+use std::sync::Arc;
+
+use common::Entity;
+use glam::{Mat4, Vec3};
+use physics::{
+    BodyType, CollisionFilter, Kinematics, Orientation, PhysicsConfig, PhysicsManager, RigidBody,
+    collision_shape::CollisionShape, event::PhysicsEvent, handle::BodyHandle,
+};
+use support::{assert_vec_near, cuboid};
+
+// Long enough for anything resting to fall asleep
+const SETTLE_STEPS: usize = 480;
+
+fn body(body_type: BodyType, shape: CollisionShape, at: Vec3) -> RigidBody {
+    RigidBody::new(
+        body_type,
+        Arc::new(shape),
+        Orientation {
+            translation: at,
+            rotation: Mat4::IDENTITY,
+        },
+        Kinematics::new(),
+        false,
+        1.0,
+        CollisionFilter::new(1, 0),
+    )
+}
+
+fn add(physics: &mut PhysicsManager, id: u64, rb: RigidBody) -> BodyHandle {
+    physics
+        .add_body(Entity::new(id), rb)
+        .expect("adding body failed")
+}
+
+// Static floor with its top at y 0
+fn add_floor(physics: &mut PhysicsManager, id: u64) -> BodyHandle {
+    let floor = body(
+        BodyType::Static,
+        cuboid(Vec3::new(5.0, 0.5, 5.0)),
+        Vec3::NEG_Y * 0.5,
+    );
+    add(physics, id, floor)
+}
+
+fn add_box(physics: &mut PhysicsManager, id: u64, at: Vec3) -> BodyHandle {
+    let rb = body(BodyType::Dynamic, cuboid(Vec3::splat(0.5)), at);
+    add(physics, id, rb)
+}
+
+fn run(physics: &mut PhysicsManager, steps: usize) {
+    for _ in 0..steps {
+        physics.step();
+    }
+}
+
+fn height(physics: &PhysicsManager, handle: BodyHandle) -> f32 {
+    physics
+        .body(handle)
+        .expect("body is gone")
+        .orient
+        .translation
+        .y
+}
+
+#[test]
+fn resting_box_falls_asleep() {
+    let mut physics = PhysicsManager::new();
+    add_floor(&mut physics, 0);
+    let cube = add_box(&mut physics, 1, Vec3::Y * 0.6);
+    run(&mut physics, SETTLE_STEPS);
+    let rb = physics.body(cube).expect("body is gone");
+    assert!(rb.is_sleeping());
+    assert_eq!(rb.kinematics.velocity, Vec3::ZERO);
+    assert!((height(&physics, cube) - 0.5).abs() <= 0.01);
+}
+
+#[test]
+fn box_dropped_on_a_large_floor_falls_asleep() {
+    // Level sized, big floors used to lose their resting contacts
+    let mut physics = PhysicsManager::new();
+    let floor = body(
+        BodyType::Static,
+        cuboid(Vec3::new(200.0, 0.5, 200.0)),
+        Vec3::NEG_Y * 0.5,
+    );
+    add(&mut physics, 0, floor);
+    let cube = add_box(&mut physics, 1, Vec3::new(30.0, 2.0, -20.0));
+    run(&mut physics, SETTLE_STEPS);
+    let rb = physics.body(cube).expect("body is gone");
+    assert!(rb.is_sleeping());
+    assert_eq!(rb.kinematics.velocity, Vec3::ZERO);
+    assert!((height(&physics, cube) - 0.5).abs() <= 0.01);
+}
+
+#[test]
+fn pushed_sleeping_box_wakes_up() {
+    let mut physics = PhysicsManager::new();
+    add_floor(&mut physics, 0);
+    let cube = add_box(&mut physics, 1, Vec3::Y * 0.6);
+    run(&mut physics, SETTLE_STEPS);
+    physics
+        .body_mut(cube)
+        .expect("body is gone")
+        .apply_impulse(Vec3::X * 5.0);
+    physics.step();
+    let rb = physics.body(cube).expect("body is gone");
+    assert!(!rb.is_sleeping());
+    assert!(rb.orient.translation.x > 0.0);
+}
+
+#[test]
+fn sleeping_stack_wakes_when_its_bottom_is_removed() {
+    let mut physics = PhysicsManager::new();
+    add_floor(&mut physics, 0);
+    let bottom = add_box(&mut physics, 1, Vec3::Y * 0.5);
+    let top = add_box(&mut physics, 2, Vec3::Y * 1.5);
+    run(&mut physics, SETTLE_STEPS);
+    assert!(physics.body(top).expect("body is gone").is_sleeping());
+    let rest = height(&physics, top);
+    physics.remove_body(bottom).expect("body is gone");
+    assert!(!physics.body(top).expect("body is gone").is_sleeping());
+    run(&mut physics, 240);
+    assert!(
+        (height(&physics, top) - 0.5).abs() <= 0.01,
+        "top box stayed at {} instead of falling from {rest}",
+        height(&physics, top)
+    );
+}
+
+#[test]
+fn box_falls_once_the_floor_is_removed() {
+    let mut physics = PhysicsManager::new();
+    let floor = add_floor(&mut physics, 0);
+    let cube = add_box(&mut physics, 1, Vec3::Y * 0.6);
+    run(&mut physics, SETTLE_STEPS);
+    assert!(physics.body(cube).expect("body is gone").is_sleeping());
+    physics.remove_body(floor).expect("body is gone");
+    run(&mut physics, 120);
+    assert!(height(&physics, cube) < 0.0);
+}
+
+#[test]
+fn landing_and_lifting_off_report_one_collision_each() {
+    let mut physics = PhysicsManager::new();
+    add_floor(&mut physics, 0);
+    let cube = add_box(&mut physics, 1, Vec3::Y);
+    let (floor, cube_entity) = (Entity::new(0), Entity::new(1));
+    run(&mut physics, SETTLE_STEPS);
+    let landed = physics.drain_events();
+    assert_eq!(
+        landed,
+        [PhysicsEvent::CollisionBegin {
+            a: floor,
+            b: cube_entity
+        }]
+    );
+    physics
+        .body_mut(cube)
+        .expect("body is gone")
+        .apply_impulse(Vec3::Y * 5.0);
+    run(&mut physics, 10);
+    assert_eq!(
+        physics.drain_events(),
+        [PhysicsEvent::CollisionEnd {
+            a: floor,
+            b: cube_entity
+        }]
+    );
+}
+
+#[test]
+fn ball_falling_through_a_sensor_is_reported_in_order() {
+    let mut physics = PhysicsManager::new();
+    let mut sensor = body(BodyType::Static, cuboid(Vec3::splat(0.5)), Vec3::Y * 2.5);
+    sensor.is_sensor = true;
+    add(&mut physics, 0, sensor);
+    let ball = body(
+        BodyType::Dynamic,
+        CollisionShape::new_sphere(Vec3::ZERO, 0.25),
+        Vec3::Y * 5.0,
+    );
+    let ball = add(&mut physics, 1, ball);
+    let (sensor, other) = (Entity::new(0), Entity::new(1));
+    run(&mut physics, 240);
+    // Sensors don't push back
+    assert!(height(&physics, ball) < 1.0);
+    let events = physics.drain_events();
+    let begin = PhysicsEvent::SensorBegin { sensor, other };
+    let end = PhysicsEvent::SensorEnd { sensor, other };
+    assert_eq!(events.first(), Some(&begin), "{events:?}");
+    assert_eq!(events.last(), Some(&end), "{events:?}");
+    let inside = &events[1..events.len() - 1];
+    assert!(!inside.is_empty());
+    assert!(
+        inside
+            .iter()
+            .all(|e| *e == PhysicsEvent::SensorPersist { sensor, other }),
+        "{events:?}"
+    );
+}
+
+#[test]
+fn handles_survive_removals_and_stale_ones_stop_resolving() {
+    let mut physics = PhysicsManager::new();
+    let first = add_box(&mut physics, 1, Vec3::ZERO);
+    let second = add_box(&mut physics, 2, Vec3::X * 3.0);
+    physics.remove_body(first).expect("body is gone");
+    // The last body got swapped into the removed one's place
+    assert_eq!(physics.entity_of(second), Some(Entity::new(2)));
+    assert_eq!(physics.handle_of(Entity::new(2)), Some(second));
+    assert!(physics.body(first).is_none());
+    assert!(physics.remove_body(first).is_none());
+    // The freed slot is reused, but under a new generation
+    let third = add_box(&mut physics, 3, Vec3::X * 6.0);
+    assert_ne!(third, first);
+    assert!(physics.body(first).is_none());
+    assert!(physics.entity_of(first).is_none());
+    assert!(physics.body_mut(first).is_none());
+    assert_eq!(physics.entity_of(third), Some(Entity::new(3)));
+    assert_eq!(physics.entity_of(second), Some(Entity::new(2)));
+}
+
+#[test]
+fn off_center_force_spins_for_one_step_only() {
+    let mut physics = PhysicsManager::with_config(PhysicsConfig {
+        gravity: Vec3::ZERO,
+        ..Default::default()
+    });
+    let dt = physics.config.dt;
+    // Unit cube weighing 1, so its inertia is 1/6 about every axis
+    let mut rb = body(BodyType::Dynamic, cuboid(Vec3::splat(0.5)), Vec3::ZERO);
+    rb.can_rotate = true;
+    let handle = add(&mut physics, 0, rb);
+    physics
+        .body_mut(handle)
+        .expect("body is gone")
+        .apply_force_at_point(Vec3::Y * 10.0, Vec3::X * 0.5);
+    physics.step();
+    let kinematics = |physics: &PhysicsManager| {
+        let rb = physics.body(handle).expect("body is gone");
+        (rb.kinematics.velocity, rb.kinematics.angular_velocity)
+    };
+    let (velocity, angular_velocity) = kinematics(&physics);
+    // Torque of 0.5 x 10 about z
+    assert_vec_near(velocity, Vec3::Y * 10.0 * dt, 1e-5);
+    assert_vec_near(angular_velocity, Vec3::Z * 6.0 * 5.0 * dt, 1e-5);
+    // The force only lasts the step it was applied for
+    physics.step();
+    let (next_velocity, next_angular_velocity) = kinematics(&physics);
+    assert_vec_near(next_velocity, velocity, 1e-6);
+    assert_vec_near(next_angular_velocity, angular_velocity, 1e-6);
+}
+
+// Two boxes 4 apart heading at each other at 4 m/s with no gravity, a with
+// filter_a and b with filter_b. Returns where they are after a second.
+fn head_on(filter_a: CollisionFilter, filter_b: CollisionFilter) -> (PhysicsManager, f32, f32) {
+    let mut physics = PhysicsManager::with_config(PhysicsConfig {
+        gravity: Vec3::ZERO,
+        ..Default::default()
+    });
+    let mut a = body(
+        BodyType::Dynamic,
+        cuboid(Vec3::splat(0.5)),
+        Vec3::NEG_X * 2.0,
+    );
+    a.filter = filter_a;
+    a.kinematics.velocity = Vec3::X * 4.0;
+    let mut b = body(BodyType::Dynamic, cuboid(Vec3::splat(0.5)), Vec3::X * 2.0);
+    b.filter = filter_b;
+    b.kinematics.velocity = Vec3::NEG_X * 4.0;
+    let a = add(&mut physics, 0, a);
+    let b = add(&mut physics, 1, b);
+    run(&mut physics, 240);
+    let x = |handle| {
+        physics
+            .body(handle)
+            .expect("body is gone")
+            .orient
+            .translation
+            .x
+    };
+    let (a_x, b_x) = (x(a), x(b));
+    (physics, a_x, b_x)
+}
+
+#[test]
+fn bodies_ignoring_each_other_pass_through() {
+    let (mut physics, a_x, b_x) = head_on(CollisionFilter::new(2, 4), CollisionFilter::new(4, 2));
+    assert!((a_x - 2.0).abs() <= 0.01, "a ended at {a_x}");
+    assert!((b_x + 2.0).abs() <= 0.01, "b ended at {b_x}");
+    assert!(physics.drain_events().is_empty());
+    // Ignoring from one side is enough
+    let (_, a_x, b_x) = head_on(CollisionFilter::new(2, 4), CollisionFilter::new(4, 0));
+    assert!(a_x > b_x, "a at {a_x} and b at {b_x} collided");
+    // And without the masks they bounce off each other
+    let (_, a_x, b_x) = head_on(CollisionFilter::new(2, 0), CollisionFilter::new(4, 0));
+    assert!(a_x < b_x, "a at {a_x} and b at {b_x} passed through");
+}
+
+#[test]
+fn raycast_skips_bodies_its_filter_ignores() {
+    let mut physics = PhysicsManager::new();
+    let mut near = body(BodyType::Static, cuboid(Vec3::splat(0.5)), Vec3::X * 2.0);
+    near.filter = CollisionFilter::new(2, 0);
+    add(&mut physics, 0, near);
+    add(
+        &mut physics,
+        1,
+        body(BodyType::Static, cuboid(Vec3::splat(0.5)), Vec3::X * 5.0),
+    );
+    let hit = |filter| {
+        physics
+            .raycast(Vec3::ZERO, Vec3::X, 10.0, &filter)
+            .map(|hit| hit.entity)
+    };
+    assert_eq!(hit(CollisionFilter::new(1, 0)), Some(Entity::new(0)));
+    assert_eq!(hit(CollisionFilter::new(1, 2)), Some(Entity::new(1)));
+    assert_eq!(hit(CollisionFilter::new(1, 3)), None);
+}
+
+#[test]
+fn update_runs_whole_steps_and_carries_the_rest() {
+    let dt = 0.01;
+    let mut physics = PhysicsManager::with_config(PhysicsConfig {
+        dt,
+        max_substeps: 4,
+        gravity: Vec3::ZERO,
+    });
+    let mut rb = body(BodyType::Dynamic, cuboid(Vec3::splat(0.5)), Vec3::ZERO);
+    rb.kinematics.velocity = Vec3::X;
+    let handle = add(&mut physics, 0, rb);
+    // Frame time, steps it should run and the alpha it should leave. The
+    // last frame is over the substep cap, so everything past 4 steps but the
+    // part of a step is dropped.
+    let frames = [
+        (0.025, 2, 0.5),
+        (0.004, 0, 0.9),
+        (0.013, 2, 0.2),
+        (0.1, 4, 0.2),
+        (0.0, 0, 0.2),
+    ];
+    let mut steps = 0;
+    for (elapsed, expected_steps, expected_alpha) in frames {
+        let ran = physics.update(elapsed);
+        assert_eq!(ran, expected_steps, "after a {elapsed}s frame");
+        steps += ran;
+        let alpha = physics.interpolation_alpha();
+        assert!((0.0..1.0).contains(&alpha), "alpha {alpha} out of range");
+        assert!(
+            (alpha - expected_alpha).abs() < 1e-3,
+            "alpha {alpha}, expected {expected_alpha}"
+        );
+        // Moving at 1 m/s, so the blend lands alpha of a step past the
+        // previous step
+        let rb = physics.body(handle).expect("body is gone");
+        let x = rb.interpolated_transform(alpha).w_axis.x;
+        let expected_x = (steps as f32 - 1.0 + alpha) * dt;
+        assert!(
+            (x - expected_x).abs() < 1e-4,
+            "x {x}, expected {expected_x}"
+        );
+    }
+}
+
+#[test]
+fn broken_config_still_steps() {
+    let mut physics = PhysicsManager::with_config(PhysicsConfig {
+        dt: f32::NAN,
+        max_substeps: 0,
+        gravity: Vec3::splat(f32::INFINITY),
+    });
+    assert_eq!(physics.config.dt, PhysicsConfig::default().dt);
+    assert_eq!(physics.config.max_substeps, 1);
+    assert_eq!(physics.config.gravity, PhysicsConfig::default().gravity);
+    assert_eq!(physics.update(physics.config.dt * 1.5), 1);
+    let alpha = physics.interpolation_alpha();
+    assert!((0.0..1.0).contains(&alpha), "alpha {alpha} out of range");
+    // Bad frame times are skipped rather than poisoning the accumulator
+    assert_eq!(physics.update(f32::NAN), 0);
+    assert_eq!(physics.update(-1.0), 0);
+    assert_eq!(physics.interpolation_alpha(), alpha);
+}
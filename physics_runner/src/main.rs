@@ -0,0 +1,206 @@
+use std::{env, fmt::Write, fs, time::Instant};
+
+use anyhow::Context;
+use common::Level;
+use glam::Quat;
+use physics::{PhysicsConfig, PhysicsManager, StepStats};
+use serde::Serialize;
+
+const USAGE: &str = "usage: physics_runner <level.ron> [--seconds 10] [--dt 0.0041667] \
+                     [--format ron|csv] [--out <path>] [--max-mean-us <us>|realtime]";
+
+enum Format {
+    Ron,
+    Csv,
+}
+
+struct Options {
+    level_path: String,
+    seconds: f32,
+    config: PhysicsConfig,
+    format: Format,
+    out_path: Option<String>,
+    // Fails the run when a step takes longer than this on average
+    max_mean_step_us: Option<f64>,
+}
+
+impl Options {
+    fn from_args(mut args: impl Iterator<Item = String>) -> anyhow::Result<Self> {
+        let mut level_path = None;
+        let mut seconds = 10.0;
+        let mut config = PhysicsConfig::default();
+        let mut format = Format::Ron;
+        let mut out_path = None;
+        let mut max_mean = None;
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().with_context(|| format!("{arg} needs a value"));
+            match arg.as_str() {
+                "--seconds" => seconds = value()?.parse().context("bad --seconds")?,
+                "--dt" => config.dt = value()?.parse().context("bad --dt")?,
+                "--format" => {
+                    format = match value()?.as_str() {
+                        "ron" => Format::Ron,
+                        "csv" => Format::Csv,
+                        other => anyhow::bail!("unknown format {other}"),
+                    }
+                }
+                "--out" => out_path = Some(value()?),
+                "--max-mean-us" => max_mean = Some(value()?),
+                _ if arg.starts_with("--") => anyhow::bail!("unknown option {arg}\n{USAGE}"),
+                _ if level_path.is_none() => level_path = Some(arg),
+                _ => anyhow::bail!("more than one level given\n{USAGE}"),
+            }
+        }
+        if config.dt <= 0.0 || seconds < 0.0 {
+            anyhow::bail!("--dt has to be positive and --seconds not negative");
+        }
+        // Realtime is only known once the dt is
+        let max_mean_step_us = match max_mean.as_deref() {
+            None => None,
+            Some("realtime") => Some(config.dt as f64 * 1e6),
+            Some(us) => Some(us.parse().context("bad --max-mean-us")?),
+        };
+        Ok(Self {
+            level_path: level_path.context(USAGE)?,
+            seconds,
+            config,
+            format,
+            out_path,
+            max_mean_step_us,
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct StepReport {
+    step: u32,
+    time_us: f64,
+    stats: StepStats,
+}
+
+#[derive(Serialize)]
+struct BodyReport {
+    // Order the body shows up in the level
+    index: usize,
+    position: [f32; 3],
+    rotation: [f32; 4],
+    velocity: [f32; 3],
+    angular_velocity: [f32; 3],
+    sleeping: bool,
+}
+
+#[derive(Serialize)]
+struct Report {
+    level: String,
+    dt: f32,
+    steps: u32,
+    total_ms: f64,
+    mean_step_us: f64,
+    max_step_us: f64,
+    step_reports: Vec<StepReport>,
+    bodies: Vec<BodyReport>,
+}
+
+impl Report {
+    fn to_csv(&self) -> String {
+        let mut out = String::new();
+        out.push_str("step,time_us,awake_bodies,pair_tests,touches,contact_points\n");
+        for s in &self.step_reports {
+            let _ = writeln!(
+                out,
+                "{},{:.1},{},{},{},{}",
+                s.step,
+                s.time_us,
+                s.stats.awake_bodies,
+                s.stats.pair_tests,
+                s.stats.touches,
+                s.stats.contact_points
+            );
+        }
+        out.push_str("\nindex,px,py,pz,qx,qy,qz,qw,vx,vy,vz,wx,wy,wz,sleeping\n");
+        for b in &self.bodies {
+            let values: Vec<String> = [
+                &b.position[..],
+                &b.rotation,
+                &b.velocity,
+                &b.angular_velocity,
+            ]
+            .concat()
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+            let _ = writeln!(out, "{},{},{}", b.index, values.join(","), b.sleeping);
+        }
+        out
+    }
+}
+
+fn run(options: &Options) -> anyhow::Result<Report> {
+    let level = Level::from_file(&options.level_path)
+        .with_context(|| format!("loading {} failed", options.level_path))?;
+    let mut physics = PhysicsManager::from_level(&level, options.config.clone())?;
+    let steps = (options.seconds / options.config.dt).ceil() as u32;
+    let mut step_reports = Vec::with_capacity(steps as usize);
+    let start = Instant::now();
+    for step in 0..steps {
+        let step_start = Instant::now();
+        physics.step();
+        step_reports.push(StepReport {
+            step,
+            time_us: step_start.elapsed().as_secs_f64() * 1e6,
+            stats: physics.step_stats().clone(),
+        });
+    }
+    let total_ms = start.elapsed().as_secs_f64() * 1e3;
+    let bodies = physics
+        .rigid_bodies()
+        .values()
+        .enumerate()
+        .map(|(index, rb)| BodyReport {
+            index,
+            position: rb.orient.translation.to_array(),
+            rotation: Quat::from_mat4(&rb.orient.rotation).to_array(),
+            velocity: rb.kinematics.velocity.to_array(),
+            angular_velocity: rb.kinematics.angular_velocity.to_array(),
+            sleeping: rb.is_sleeping(),
+        })
+        .collect();
+    let step_times = step_reports.iter().map(|s| s.time_us);
+    Ok(Report {
+        level: options.level_path.clone(),
+        dt: options.config.dt,
+        steps,
+        total_ms,
+        mean_step_us: step_times.clone().sum::<f64>() / steps.max(1) as f64,
+        max_step_us: step_times.fold(0.0, f64::max),
+        step_reports,
+        bodies,
+    })
+}
+
+fn main() -> anyhow::Result<()> {
+    let options = Options::from_args(env::args().skip(1))?;
+    let report = run(&options)?;
+    eprintln!(
+        "{}: {} steps in {:.1} ms, {:.1} us mean, {:.1} us max",
+        report.level, report.steps, report.total_ms, report.mean_step_us, report.max_step_us
+    );
+    let output = match options.format {
+        Format::Ron => ron::ser::to_string_pretty(&report, Default::default())?,
+        Format::Csv => report.to_csv(),
+    };
+    match &options.out_path {
+        Some(path) => fs::write(path, output).with_context(|| format!("writing {path} failed"))?,
+        None => println!("{output}"),
+    }
+    if let Some(max) = options.max_mean_step_us
+        && report.mean_step_us > max
+    {
+        anyhow::bail!(
+            "{}: steps took {:.1} us on average, over the {max:.1} us allowed",
+            report.level,
+            report.mean_step_us
+        );
+    }
+    Ok(())
+}
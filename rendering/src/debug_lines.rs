@@ -0,0 +1,205 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context;
+use ash::vk;
+use gpu_allocator::{MemoryLocation, vulkan::Allocator};
+use naga::ShaderStage;
+
+use crate::{
+    tex_mesh::{CameraData, TexMeshPass, Vertex},
+    vkraii::{
+        device::{DeviceDropper, DeviceRaii},
+        pipeline::ShaderRaii,
+        resource::BufferRaii,
+    },
+};
+
+// Line list drawn inside the TexMeshPass render pass after the meshes. Depth
+// testing is off so the lines show through everything.
+pub struct DebugLinePass {
+    vertex_buffer: Option<BufferRaii>,
+    pub pipeline_layout: vk::PipelineLayout,
+    pub pipeline: vk::Pipeline,
+    device_d: Arc<DeviceDropper>,
+}
+
+impl DebugLinePass {
+    pub fn new(device: &DeviceRaii, tex_mesh_pass: &TexMeshPass) -> anyhow::Result<Self> {
+        let vert_shader = ShaderRaii::load_glsl_str(
+            &device.device_d,
+            include_str!("shaders/debug_line.vert"),
+            ShaderStage::Vertex,
+        )?;
+        let frag_shader = ShaderRaii::load_glsl_str(
+            &device.device_d,
+            include_str!("shaders/triangle.frag"),
+            ShaderStage::Fragment,
+        )?;
+        // Takes the same camera set as the mesh pipeline
+        let pipeline_layout = unsafe {
+            device.device_d.device.create_pipeline_layout(
+                &vk::PipelineLayoutCreateInfo::default()
+                    .set_layouts(&[tex_mesh_pass.descriptor_set_layouts[0].layout]),
+                None,
+            )?
+        };
+        let pipeline = unsafe {
+            device
+                .device_d
+                .device
+                .create_graphics_pipelines(
+                    vk::PipelineCache::null(),
+                    &[vk::GraphicsPipelineCreateInfo::default()
+                        .color_blend_state(
+                            &vk::PipelineColorBlendStateCreateInfo::default()
+                                .attachments(&[vk::PipelineColorBlendAttachmentState::default()
+                                    .color_write_mask(vk::ColorComponentFlags::RGBA)]),
+                        )
+                        .depth_stencil_state(
+                            &vk::PipelineDepthStencilStateCreateInfo::default()
+                                .depth_test_enable(false)
+                                .depth_write_enable(false)
+                                .max_depth_bounds(1.0),
+                        )
+                        .dynamic_state(
+                            &vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&[
+                                vk::DynamicState::VIEWPORT,
+                                vk::DynamicState::SCISSOR,
+                            ]),
+                        )
+                        .input_assembly_state(
+                            &vk::PipelineInputAssemblyStateCreateInfo::default()
+                                .topology(vk::PrimitiveTopology::LINE_LIST),
+                        )
+                        .layout(pipeline_layout)
+                        .multisample_state(
+                            &vk::PipelineMultisampleStateCreateInfo::default()
+                                .sample_shading_enable(false)
+                                .rasterization_samples(vk::SampleCountFlags::TYPE_1),
+                        )
+                        .rasterization_state(
+                            &vk::PipelineRasterizationStateCreateInfo::default()
+                                .cull_mode(vk::CullModeFlags::NONE)
+                                .depth_bias_enable(false)
+                                .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+                                .line_width(1.0)
+                                .polygon_mode(vk::PolygonMode::FILL),
+                        )
+                        .render_pass(tex_mesh_pass.render_pass)
+                        .stages(&[
+                            vk::PipelineShaderStageCreateInfo::default()
+                                .module(vert_shader.module)
+                                .name(c"main")
+                                .stage(vk::ShaderStageFlags::VERTEX),
+                            vk::PipelineShaderStageCreateInfo::default()
+                                .module(frag_shader.module)
+                                .name(c"main")
+                                .stage(vk::ShaderStageFlags::FRAGMENT),
+                        ])
+                        .vertex_input_state(
+                            &vk::PipelineVertexInputStateCreateInfo::default()
+                                .vertex_attribute_descriptions(&Vertex::attribute_descs())
+                                .vertex_binding_descriptions(&[
+                                    vk::VertexInputBindingDescription::default()
+                                        .binding(0)
+                                        .input_rate(vk::VertexInputRate::VERTEX)
+                                        .stride(size_of::<Vertex>() as _),
+                                ]),
+                        )
+                        .viewport_state(
+                            &vk::PipelineViewportStateCreateInfo::default()
+                                .viewport_count(1)
+                                .scissor_count(1),
+                        )],
+                    None,
+                )
+                .map_err(|(_, e)| e)?[0]
+        };
+        drop(vert_shader);
+        drop(frag_shader);
+
+        Ok(Self {
+            vertex_buffer: None,
+            pipeline_layout,
+            pipeline,
+            device_d: device.device_d.clone(),
+        })
+    }
+
+    // Two vertices per line. Has to be called between begin and end of the
+    // TexMeshPass. The buffer is rewritten every frame, which is fine as long
+    // as the frame is waited on before the next one is recorded.
+    pub fn draw_lines(
+        &mut self,
+        vertices: &[Vertex],
+        cam_data: &CameraData,
+        allocator: &Arc<Mutex<Allocator>>,
+        command_buffer: vk::CommandBuffer,
+    ) -> anyhow::Result<()> {
+        if vertices.is_empty() {
+            return Ok(());
+        }
+        let bytes: &[u8] = bytemuck::cast_slice(vertices);
+        if self
+            .vertex_buffer
+            .as_ref()
+            .is_none_or(|b| b.size < bytes.len() as u64)
+        {
+            self.vertex_buffer = Some(BufferRaii::new(
+                &self.device_d,
+                allocator,
+                &vk::BufferCreateInfo::default()
+                    .size(bytes.len().next_power_of_two() as _)
+                    .usage(vk::BufferUsageFlags::VERTEX_BUFFER),
+                MemoryLocation::CpuToGpu,
+            )?);
+        }
+        let vertex_buffer = self
+            .vertex_buffer
+            .as_mut()
+            .with_context(|| "debug line buffer missing")?;
+        vertex_buffer
+            .mem
+            .allocation
+            .mapped_slice_mut()
+            .with_context(|| "unable to write to debug line buffer")?[..bytes.len()]
+            .copy_from_slice(bytes);
+        unsafe {
+            self.device_d.device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline,
+            );
+            self.device_d.device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                &[cam_data.dset.set],
+                &[],
+            );
+            self.device_d.device.cmd_bind_vertex_buffers(
+                command_buffer,
+                0,
+                &[vertex_buffer.buffer],
+                &[0],
+            );
+            self.device_d
+                .device
+                .cmd_draw(command_buffer, vertices.len() as _, 1, 0, 0);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for DebugLinePass {
+    fn drop(&mut self) {
+        self.vertex_buffer = None;
+        unsafe {
+            self.device_d.device.destroy_pipeline(self.pipeline, None);
+            self.device_d
+                .device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+    }
+}
@@ -9,7 +9,8 @@ use winit::window::Window;
 
 use crate::{
     camera::Camera,
-    tex_mesh::{GpuMesh, Mesh, TexMeshPass},
+    debug_lines::DebugLinePass,
+    tex_mesh::{GpuMesh, Mesh, TexMeshPass, Vertex},
     vkraii::{
         command::CommandBufferRaii,
         device::DeviceRaii,
@@ -19,6 +20,7 @@ use crate::{
 };
 
 pub mod camera;
+pub mod debug_lines;
 pub mod tex_mesh;
 mod vkraii;
 
@@ -55,8 +57,11 @@ pub struct RenderingManager {
     pub per_frame_datas: Vec<PerFrameData>,
     pub meshes: IndexMap<Entity, GpuMesh>,
     pub camera: Camera,
+    // Drawn as a line list over the scene, two vertices per line
+    pub debug_lines: Vec<Vertex>,
     textures: IndexMap<String, ImageRaii>,
     pipeline: TexMeshPass,
+    debug_line_pass: DebugLinePass,
     deferred_cb: Option<CommandBufferRaii>,
     swapchain: SwapchainRaii,
     device: DeviceRaii,
@@ -67,6 +72,7 @@ impl RenderingManager {
         let mut device = DeviceRaii::new(window)?;
         let swapchain = SwapchainRaii::new(&device.device_d)?;
         let pipeline = TexMeshPass::new(&mut device, swapchain.format)?;
+        let debug_line_pass = DebugLinePass::new(&device, &pipeline)?;
         let camera = Camera {
             eye: glam::vec3(0.0, 0.0, 2.0),
             dir: -glam::Vec3::Z,
@@ -82,8 +88,10 @@ impl RenderingManager {
             per_frame_datas,
             meshes: Default::default(),
             camera,
+            debug_lines: vec![],
             textures: Default::default(),
             pipeline,
+            debug_line_pass,
             deferred_cb: None,
             swapchain,
             device,
@@ -120,6 +128,7 @@ impl RenderingManager {
         Ok(gpu_mesh)
     }
 
+    #[allow(dead_code)]
     fn load_image(&mut self, path: &str) -> anyhow::Result<()> {
         if self.textures.contains_key(path) {
             return Ok(());
@@ -260,6 +269,12 @@ impl RenderingManager {
             .bind_camera_data(&cam_dset_data, command_buffer.command_buffer);
         self.pipeline
             .draw_meshes(self.meshes.values(), command_buffer.command_buffer);
+        self.debug_line_pass.draw_lines(
+            &self.debug_lines,
+            &cam_dset_data,
+            &self.device.allocator,
+            command_buffer.command_buffer,
+        )?;
         self.pipeline.end(command_buffer.command_buffer);
         let task = self.device.run_commands(vec![command_buffer])?;
         self.device.wait_on_task(task)?;
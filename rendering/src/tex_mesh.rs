@@ -1,5 +1,6 @@
 use std::{
     collections::HashMap,
+    f32::consts::{FRAC_PI_2, TAU},
     mem::offset_of,
     sync::{Arc, Mutex},
 };
@@ -20,6 +21,9 @@ use crate::{
     },
 };
 
+const SPHERE_SEGMENTS: u16 = 24;
+const HEMISPHERE_RINGS: u16 = 8;
+
 #[derive(Debug, Clone, Copy, bytemuck::NoUninit)]
 #[repr(C)]
 pub struct Vertex {
@@ -133,6 +137,86 @@ impl Mesh {
             Self::new_rectangle(c - z, -x, y),
         ])
     }
+
+    // Arbitrary triangle list, vertices coloured by the facing of the
+    // triangles around them so slopes stand out
+    pub fn new_triangle_mesh(vertices: &[glam::Vec3], indices: &[u16]) -> Self {
+        let mut normals = vec![glam::Vec3::ZERO; vertices.len()];
+        for tri in indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| vertices[tri[i] as usize]);
+            let normal = (b - a).cross(c - a);
+            for &i in tri {
+                normals[i as usize] += normal;
+            }
+        }
+        let vertices = vertices
+            .iter()
+            .zip(normals)
+            .map(|(&pos, normal)| Vertex {
+                pos,
+                color: normal.normalize_or_zero() * 0.5 + 0.5,
+            })
+            .collect();
+        Self {
+            vertices,
+            indices: indices.to_vec(),
+        }
+    }
+
+    pub fn new_uv_sphere(c: glam::Vec3, r: f32) -> Self {
+        Self::new_capsule(c, c, r)
+    }
+
+    // Two hemispheres around a and b joined by a cylinder, built ring by ring
+    // from the b pole down to the a pole
+    pub fn new_capsule(a: glam::Vec3, b: glam::Vec3, r: f32) -> Self {
+        let axis = (b - a).normalize_or(glam::Vec3::Y);
+        let u = axis.any_orthonormal_vector();
+        let v = axis.cross(u);
+        let mut rings = vec![];
+        for i in 1..=HEMISPHERE_RINGS {
+            let phi = i as f32 / HEMISPHERE_RINGS as f32 * FRAC_PI_2;
+            rings.push((b, b + axis * r * phi.cos(), r * phi.sin()));
+        }
+        // A sphere shares the equator between both halves
+        let first_lower = if a == b { 1 } else { 0 };
+        for i in first_lower..HEMISPHERE_RINGS {
+            let phi = i as f32 / HEMISPHERE_RINGS as f32 * FRAC_PI_2;
+            rings.push((a, a - axis * r * phi.sin(), r * phi.cos()));
+        }
+        let vertex = |sphere_c: glam::Vec3, pos: glam::Vec3| Vertex {
+            pos,
+            color: (pos - sphere_c).normalize_or_zero() * 0.5 + 0.5,
+        };
+        let mut vertices = vec![vertex(b, b + axis * r)];
+        for &(sphere_c, ring_c, ring_r) in &rings {
+            for j in 0..SPHERE_SEGMENTS {
+                let theta = j as f32 / SPHERE_SEGMENTS as f32 * TAU;
+                let pos = ring_c + (u * theta.cos() + v * theta.sin()) * ring_r;
+                vertices.push(vertex(sphere_c, pos));
+            }
+        }
+        vertices.push(vertex(a, a - axis * r));
+
+        let bottom_pole = vertices.len() as u16 - 1;
+        let ring_idx = |ring: u16, j: u16| 1 + ring * SPHERE_SEGMENTS + j % SPHERE_SEGMENTS;
+        let last_ring = rings.len() as u16 - 1;
+        let mut indices = vec![];
+        for j in 0..SPHERE_SEGMENTS {
+            indices.extend([0, ring_idx(0, j), ring_idx(0, j + 1)]);
+            for ring in 0..last_ring {
+                let (up0, up1) = (ring_idx(ring, j), ring_idx(ring, j + 1));
+                let (lo0, lo1) = (ring_idx(ring + 1, j), ring_idx(ring + 1, j + 1));
+                indices.extend([up0, lo0, lo1, up0, lo1, up1]);
+            }
+            indices.extend([
+                ring_idx(last_ring, j),
+                bottom_pole,
+                ring_idx(last_ring, j + 1),
+            ]);
+        }
+        Self { vertices, indices }
+    }
 }
 
 pub struct GpuMesh {
@@ -75,8 +75,8 @@ pub fn create_surface(
 ) -> anyhow::Result<vk::SurfaceKHR> {
     let surface = unsafe {
         ash_window::create_surface(
-            &entry,
-            &instance,
+            entry,
+            instance,
             window
                 .display_handle()
                 .with_context(|| "get display handle failed")?
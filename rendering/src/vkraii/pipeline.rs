@@ -29,7 +29,7 @@ impl ShaderRaii {
     ) -> anyhow::Result<Self> {
         let mut frontend = glsl::Frontend::default();
         let options = glsl::Options::from(stage);
-        let ir = frontend.parse(&options, &glsl_str)?;
+        let ir = frontend.parse(&options, glsl_str)?;
         let mut validator = Validator::new(ValidationFlags::all(), Capabilities::empty());
         let module_info = validator.validate(&ir)?;
         let spv_words = spv::write_vec(&ir, &module_info, &spv::Options::default(), None)?;
@@ -45,6 +45,7 @@ impl ShaderRaii {
         })
     }
 
+    #[allow(dead_code)]
     pub fn load_glsl(device_d: &Arc<DeviceDropper>, path: &str) -> anyhow::Result<Self> {
         let shader_stage = if path.ends_with(".vert") {
             ShaderStage::Vertex
@@ -83,11 +84,13 @@ impl Drop for DescriptorPoolDropper {
     }
 }
 
+type SharedSetPool = Arc<Mutex<Vec<(vk::DescriptorSet, Arc<DescriptorPoolDropper>)>>>;
+
 pub struct DescriptorSetLayoutRaii {
     pub bindings: Vec<(vk::DescriptorType, u32)>,
     pub layout: vk::DescriptorSetLayout,
     pub alloc_batch_size: u32,
-    pool: Arc<Mutex<Vec<(vk::DescriptorSet, Arc<DescriptorPoolDropper>)>>>,
+    pool: SharedSetPool,
     device_d: Arc<DeviceDropper>,
 }
 
@@ -190,7 +193,7 @@ impl Drop for DescriptorSetLayoutRaii {
 pub struct DescriptorSetRaii {
     pub set: vk::DescriptorSet,
     pool_d: Arc<DescriptorPoolDropper>,
-    pool: Arc<Mutex<Vec<(vk::DescriptorSet, Arc<DescriptorPoolDropper>)>>>,
+    pool: SharedSetPool,
 }
 
 impl DescriptorSetRaii {
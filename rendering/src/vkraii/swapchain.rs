@@ -22,10 +22,10 @@ const COLOR_SPACES: [vk::ColorSpaceKHR; 2] = [
 ];
 
 fn choose_surface_format(
-    surface_formats: &Vec<vk::SurfaceFormatKHR>,
+    surface_formats: &[vk::SurfaceFormatKHR],
 ) -> anyhow::Result<vk::SurfaceFormatKHR> {
     let surface_formats: Vec<_> = surface_formats
-        .into_iter()
+        .iter()
         .filter(|s| COLOR_SPACES.contains(&s.color_space))
         .collect();
     let surface_format = match HDR_FORMATS.iter().find_map(|format| {
@@ -37,20 +37,17 @@ fn choose_surface_format(
         })
     }) {
         Some(sf) => sf,
-        None => {
-            let sf = SDR_FORMATS
-                .iter()
-                .find_map(|format| {
-                    surface_formats.iter().find_map(|s| {
-                        if s.format == *format {
-                            return Some(**s);
-                        }
-                        None
-                    })
+        None => SDR_FORMATS
+            .iter()
+            .find_map(|format| {
+                surface_formats.iter().find_map(|s| {
+                    if s.format == *format {
+                        return Some(**s);
+                    }
+                    None
                 })
-                .with_context(|| "no supported surface format")?;
-            sf
-        }
+            })
+            .with_context(|| "no supported surface format")?,
     };
     Ok(surface_format)
 }
@@ -134,7 +131,7 @@ impl SwapchainRaii {
         let sc_img_count = std::cmp::min(
             sc_caps.min_image_count + 1,
             if sc_caps.max_image_count == 0 {
-                std::u32::MAX
+                u32::MAX
             } else {
                 sc_caps.max_image_count
             },